@@ -1,4 +1,7 @@
+use crate::highlight::Highlighter;
 use crate::utils;
+use cursive::theme::{BaseColor, Color, Style};
+use cursive::utils::markup::StyledString;
 
 #[derive(Debug, Clone)]
 pub struct Article {
@@ -10,9 +13,12 @@ pub struct Article {
     pub unread: i8,
     pub feed_id: String,
     pub pub_date: i64,
+    /// Set by a `highlight` rule
+    pub highlighted: bool,
 }
 
 impl Article {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         id: String,
         link: String,
@@ -22,6 +28,7 @@ impl Article {
         unread: i8,
         feed_id: String,
         pub_date: i64,
+        highlighted: bool,
     ) -> Self {
         Self {
             id,
@@ -32,6 +39,7 @@ impl Article {
             unread,
             feed_id,
             pub_date,
+            highlighted,
         }
     }
 
@@ -39,13 +47,27 @@ impl Article {
         self.unread == 1
     }
 
-    pub fn draw(&self) -> String {
+    /// The line shown in the content list, with highlighted keywords in the
+    /// title and the whole line coloured when a rule highlighted it.
+    pub fn draw(&self, highlighter: &Highlighter) -> StyledString {
         let unread = if self.unread() { "N" } else { " " };
-        format!(
-            "{} {} {}",
-            utils::formatted_pub_date(self.pub_date),
-            unread,
-            self.title,
-        )
+        let style = if self.highlighted {
+            Style::from(Color::Light(BaseColor::Yellow))
+        } else {
+            Style::none()
+        };
+        let mut line = StyledString::styled(
+            format!("{} {} ", utils::formatted_pub_date(self.pub_date), unread),
+            style,
+        );
+        highlighter.append(&mut line, &self.title, style);
+        line
+    }
+
+    /// Whether the article belongs in the "Highlighted" list.
+    pub fn is_highlighted(&self, highlighter: &Highlighter) -> bool {
+        self.highlighted
+            || highlighter.is_match(&self.title)
+            || highlighter.is_match_html(&self.content)
     }
 }
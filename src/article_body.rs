@@ -0,0 +1,330 @@
+use crate::highlight::{self, Highlighter};
+use crate::images::{self, Image, Pictures};
+use crate::utils;
+use cursive::theme::{Effect, PaletteColor, Style};
+use cursive::utils::markup::StyledString;
+use cursive::view::{View, ViewWrapper};
+use cursive::views::TextView;
+use cursive::{wrap_impl, Vec2};
+use html2text::render::text_renderer::{RichAnnotation, TaggedLine, TextDecorator};
+use std::cell::RefCell;
+use std::rc::Rc;
+use url::Url;
+
+/// Narrowest width the article is wrapped to, html2text needs some room for
+/// nested blocks like tables and quotes.
+const MIN_WIDTH: usize = 20;
+
+/// Rich decorator which adds `[n]` markers after the link text and collects
+/// the targets, resolved against the article URL, to be listed as footnotes.
+#[derive(Clone, Debug, Default)]
+pub struct ArticleDecorator {
+    base: Option<Url>,
+    links: Rc<RefCell<Vec<String>>>,
+    open_links: Vec<Option<usize>>,
+}
+
+impl ArticleDecorator {
+    pub fn new(base: Option<Url>) -> ArticleDecorator {
+        ArticleDecorator {
+            base,
+            links: Rc::new(RefCell::new(Vec::new())),
+            open_links: Vec::new(),
+        }
+    }
+
+    /// The list is shared with the decorators html2text creates for nested
+    /// blocks, so it's complete once the document has been rendered.
+    pub fn links(&self) -> Rc<RefCell<Vec<String>>> {
+        Rc::clone(&self.links)
+    }
+}
+
+impl TextDecorator for ArticleDecorator {
+    type Annotation = RichAnnotation;
+
+    fn decorate_link_start(&mut self, url: &str) -> (String, Self::Annotation) {
+        let link = utils::resolve_link(self.base.as_ref(), url);
+        let number = link.clone().map(|link| {
+            let mut links = self.links.borrow_mut();
+            match links.iter().position(|l| *l == link) {
+                Some(index) => index + 1,
+                None => {
+                    links.push(link);
+                    links.len()
+                }
+            }
+        });
+        self.open_links.push(number);
+        match link {
+            Some(link) => ("".to_string(), RichAnnotation::Link(link)),
+            None => ("".to_string(), RichAnnotation::Default),
+        }
+    }
+
+    fn decorate_link_end(&mut self) -> String {
+        match self.open_links.pop() {
+            Some(Some(number)) => format!("[{}]", number),
+            _ => "".to_string(),
+        }
+    }
+
+    fn decorate_em_start(&mut self) -> (String, Self::Annotation) {
+        ("".to_string(), RichAnnotation::Emphasis)
+    }
+
+    fn decorate_em_end(&mut self) -> String {
+        "".to_string()
+    }
+
+    fn decorate_strong_start(&mut self) -> (String, Self::Annotation) {
+        ("".to_string(), RichAnnotation::Strong)
+    }
+
+    fn decorate_strong_end(&mut self) -> String {
+        "".to_string()
+    }
+
+    fn decorate_strikeout_start(&mut self) -> (String, Self::Annotation) {
+        ("".to_string(), RichAnnotation::Strikeout)
+    }
+
+    fn decorate_strikeout_end(&mut self) -> String {
+        "".to_string()
+    }
+
+    fn decorate_code_start(&mut self) -> (String, Self::Annotation) {
+        ("".to_string(), RichAnnotation::Code)
+    }
+
+    fn decorate_code_end(&mut self) -> String {
+        "".to_string()
+    }
+
+    fn decorate_preformat_first(&mut self) -> Self::Annotation {
+        RichAnnotation::Preformat(false)
+    }
+
+    fn decorate_preformat_cont(&mut self) -> Self::Annotation {
+        RichAnnotation::Preformat(true)
+    }
+
+    fn decorate_image(&mut self, title: &str) -> (String, Self::Annotation) {
+        (format!("[{}]", title), RichAnnotation::Image)
+    }
+
+    fn header_prefix(&mut self, level: usize) -> String {
+        "#".repeat(level) + " "
+    }
+
+    fn quote_prefix(&mut self) -> String {
+        "> ".to_string()
+    }
+
+    fn unordered_item_prefix(&mut self) -> String {
+        "* ".to_string()
+    }
+
+    fn ordered_item_prefix(&mut self, i: i64) -> String {
+        format!("{}. ", i)
+    }
+
+    fn finalise(self) -> Vec<TaggedLine<RichAnnotation>> {
+        // Footnotes are rendered by the article view from `links()`
+        Vec::new()
+    }
+
+    fn make_subblock_decorator(&self) -> Self {
+        ArticleDecorator {
+            base: self.base.clone(),
+            links: self.links(),
+            open_links: Vec::new(),
+        }
+    }
+}
+
+fn annotation_style(annotation: &RichAnnotation) -> Style {
+    match annotation {
+        RichAnnotation::Default => Style::none(),
+        RichAnnotation::Link(_) => Style::from(PaletteColor::Secondary).combine(Effect::Underline),
+        RichAnnotation::Image => Style::from(PaletteColor::Tertiary),
+        RichAnnotation::Emphasis => Style::from(Effect::Italic),
+        RichAnnotation::Strong => Style::from(Effect::Bold),
+        RichAnnotation::Strikeout => Style::from(Effect::Strikethrough),
+        RichAnnotation::Code | RichAnnotation::Preformat(_) => {
+            Style::from(PaletteColor::TitleSecondary)
+        }
+    }
+}
+
+/// Headers and quotes aren't annotated by html2text, they are recognised by
+/// the prefix the decorator gives to each of their lines.
+fn line_style(line: &str) -> Style {
+    let hashes = line.chars().take_while(|c| *c == '#').count();
+    if hashes > 0 && line[hashes..].starts_with(' ') {
+        Style::from(PaletteColor::TitlePrimary).combine(Effect::Bold)
+    } else if line.starts_with("> ") {
+        Style::from(PaletteColor::Tertiary).combine(Effect::Italic)
+    } else {
+        Style::none()
+    }
+}
+
+/// Renders article HTML wrapped to `width` columns, returning the styled
+/// text together with the footnote links in the order of their markers.
+pub fn render(html: &str, base: Option<Url>, width: usize) -> (StyledString, Vec<String>) {
+    render_with_images(
+        html,
+        base,
+        width,
+        &[],
+        &mut Pictures::default(),
+        false,
+        &Highlighter::default(),
+    )
+}
+
+/// Like `render`, for HTML prepared by `images::mark_images`: each image
+/// marker is replaced by a preview if asked for and the image is cached, or
+/// by a placeholder with its alt text.
+fn render_with_images(
+    html: &str,
+    base: Option<Url>,
+    width: usize,
+    images: &[Image],
+    pictures: &mut Pictures,
+    previews: bool,
+    highlighter: &Highlighter,
+) -> (StyledString, Vec<String>) {
+    let width = width.max(MIN_WIDTH);
+    let decorator = ArticleDecorator::new(base);
+    let links = decorator.links();
+    let lines = html2text::parse(html.as_bytes())
+        .render(width, decorator)
+        .into_lines();
+
+    let mut text = StyledString::new();
+    for line in lines {
+        let plain: String = line.tagged_strings().map(|ts| ts.s.as_str()).collect();
+        if let Some(image) = images::image_at(&plain).and_then(|index| images.get(index)) {
+            let picture = Some(image)
+                .filter(|_| previews)
+                .and_then(|image| pictures.get(image));
+            match picture {
+                Some(picture) => text.append(images::render_half_blocks(picture, width)),
+                None => {
+                    text.append_styled(image.placeholder(), PaletteColor::Tertiary);
+                    text.append_plain("\n");
+                }
+            }
+            continue;
+        }
+        let style = line_style(&plain);
+        let ranges = highlighter.ranges(&plain);
+        let mut offset = 0;
+        for ts in line.tagged_strings() {
+            let span_style = ts.tag.iter().fold(style, |acc, annotation| {
+                acc.combine(annotation_style(annotation))
+            });
+            highlight::append_ranges(&mut text, &ts.s, offset, span_style, &ranges);
+            offset += ts.s.len();
+        }
+        text.append_plain("\n");
+    }
+
+    let links = links.borrow().clone();
+    (text, links)
+}
+
+/// Article text which is wrapped by html2text again whenever the available
+/// width changes.
+pub struct ArticleBody {
+    html: String,
+    base: Option<Url>,
+    width: usize,
+    links: Vec<String>,
+    images: Vec<Image>,
+    pictures: Pictures,
+    previews: bool,
+    highlighter: Highlighter,
+    view: TextView,
+}
+
+impl ArticleBody {
+    pub fn new(html: &str, base: Option<Url>) -> Self {
+        Self::with_options(html, base, false, Highlighter::default())
+    }
+
+    /// With `previews` the images of the article are drawn inline once they
+    /// are in the cache, see `images::prefetch`, and keywords matched by
+    /// `highlighter` are coloured.
+    pub fn with_options(
+        html: &str,
+        base: Option<Url>,
+        previews: bool,
+        highlighter: Highlighter,
+    ) -> Self {
+        let width = 80;
+        let (html, images) = images::mark_images(html, base.as_ref());
+        let mut pictures = Pictures::default();
+        let (text, links) = render_with_images(
+            &html,
+            base.clone(),
+            width,
+            &images,
+            &mut pictures,
+            previews,
+            &highlighter,
+        );
+        Self {
+            html,
+            base,
+            width,
+            links,
+            images,
+            pictures,
+            previews,
+            highlighter,
+            view: TextView::new(text),
+        }
+    }
+
+    pub fn links(&self) -> &[String] {
+        &self.links
+    }
+
+    pub fn images(&self) -> &[Image] {
+        &self.images
+    }
+
+    /// Renders the article again on the next layout, e.g. after images
+    /// have been downloaded.
+    pub fn invalidate(&mut self) {
+        self.width = 0;
+    }
+}
+
+impl ViewWrapper for ArticleBody {
+    wrap_impl!(self.view: TextView);
+
+    fn wrap_required_size(&mut self, req: Vec2) -> Vec2 {
+        if req.x != self.width {
+            self.width = req.x;
+            let (text, _) = render_with_images(
+                &self.html,
+                self.base.clone(),
+                self.width,
+                &self.images,
+                &mut self.pictures,
+                self.previews,
+                &self.highlighter,
+            );
+            self.view.set_content(text);
+        }
+        self.view.required_size(req)
+    }
+
+    fn wrap_needs_relayout(&self) -> bool {
+        self.width == 0 || self.view.needs_relayout()
+    }
+}
@@ -0,0 +1,59 @@
+//! Copying text to the system clipboard through the terminal, which also
+//! works over SSH.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Sets the clipboard with the OSC 52 escape sequence and, when configured,
+/// also pipes `text` to `command`, e.g. `wl-copy` or `xclip -sel clip`, for
+/// terminals that ignore OSC 52.
+pub fn copy(text: &str, command: Option<&str>) -> Result<(), String> {
+    let osc = osc52(text);
+    let written = OpenOptions::new()
+        .write(true)
+        .open("/dev/tty")
+        .and_then(|mut tty| tty.write_all(osc.as_bytes()).and_then(|_| tty.flush()));
+
+    match command {
+        Some(command) => pipe_to(command, text),
+        None => written.map_err(|error| format!("Couldn't write to the terminal: {}", error)),
+    }
+}
+
+fn osc52(text: &str) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", STANDARD.encode(text));
+    if env::var_os("TMUX").is_some() {
+        // tmux only passes escapes through to the outer terminal when
+        // wrapped in DCS with every ESC doubled
+        format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+    } else {
+        sequence
+    }
+}
+
+fn pipe_to(command: &str, text: &str) -> Result<(), String> {
+    let mut parts = command.split_whitespace();
+    let program = parts.next().ok_or("Empty clipboard command")?;
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|error| format!("Couldn't run `{}`: {}", command, error))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .map_err(|error| format!("Couldn't write to `{}`: {}", command, error))?;
+    }
+    let status = child.wait().map_err(|error| error.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("`{}` failed: {}", command, status))
+    }
+}
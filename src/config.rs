@@ -1,19 +1,347 @@
+use crate::utils;
 use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::{error::Error, fs};
 
+/// Name of the implicit profile built from the top level credentials when
+/// `config.toml` doesn't define any `[profiles.<name>]` tables.
+pub const DEFAULT_PROFILE: &str = "default";
+
 #[derive(Deserialize, Debug, Default, Clone)]
 pub struct Config {
     pub fresh_rss_api_url: Option<String>,
     pub fresh_rss_api_user: Option<String>,
     pub fresh_rss_api_password: Option<String>,
+    /// Browser command, `%u` is replaced by the link which is otherwise
+    /// appended, e.g. `firefox --new-tab %u`
     pub browser: Option<String>,
+    /// Run `browser` in the foreground with the UI suspended, for terminal
+    /// browsers like `w3m`
+    #[serde(default)]
+    pub terminal_browser: bool,
+    /// Command used to play enclosures, e.g. `mpv --no-video`
+    pub player: Option<String>,
+    /// Command the yanked text is piped to besides the OSC 52 escape,
+    /// e.g. `wl-copy` or `xclip -selection clipboard`
+    pub clipboard_command: Option<String>,
+    /// Where downloaded enclosures are saved, defaults to the downloads dir
+    pub download_dir: Option<PathBuf>,
+    /// How images in articles are shown: `none`, `halfblocks` or `auto`
+    #[serde(default)]
+    pub image_preview: ImagePreview,
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    #[serde(default)]
+    pub retention: Retention,
+    #[serde(default)]
+    pub layout: Layout,
+    /// Per-feed settings keyed by feed id or title
+    #[serde(default)]
+    pub feeds: BTreeMap<String, FeedSettings>,
+    #[serde(default)]
+    pub export: Export,
+    /// Services articles can be sent to, keyed by the name shown in the UI
+    #[serde(default)]
+    pub share: BTreeMap<String, ShareTarget>,
+    /// `[[rules]]` applied to new articles during sync
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// `[[highlights]]` coloured in article titles and bodies
+    #[serde(default)]
+    pub highlights: Vec<Highlight>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImagePreview {
+    /// Images are only listed by their alt text
+    #[default]
+    None,
+    /// Images are drawn with Unicode half blocks, two pixels per cell
+    Halfblocks,
+    /// Half blocks when the terminal advertises true colour support
+    Auto,
+}
+
+impl ImagePreview {
+    pub fn enabled(&self) -> bool {
+        match self {
+            ImagePreview::None => false,
+            ImagePreview::Halfblocks => true,
+            ImagePreview::Auto => std::env::var("COLORTERM")
+                .map(|value| value == "truecolor" || value == "24bit")
+                .unwrap_or(false),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct FeedSettings {
+    /// Fetch the linked page and show its main content instead of the
+    /// summary from the feed
+    #[serde(default)]
+    pub full_content: bool,
+}
+
+/// `[retention]` settings, pruning is disabled unless some are set.
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct Retention {
+    /// Delete read articles this many days after they were read
+    pub read_days: Option<i64>,
+    /// Keep at most this many read articles per feed
+    pub max_per_feed: Option<i64>,
+    /// Run a full `VACUUM` at most once per this many days, otherwise only
+    /// an incremental one is done after pruning
+    pub vacuum_interval_days: Option<i64>,
+}
+
+/// `[layout]` settings for the main screen.
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct Layout {
+    #[serde(default)]
+    pub mode: LayoutMode,
+    /// Seconds an article has to stay in the preview pane before it's
+    /// marked read, previewed articles stay unread when unset
+    pub mark_read_delay: Option<u64>,
+    /// Narrowest terminal the preview pane is shown in, defaults to 120
+    /// columns
+    pub min_width: Option<usize>,
+}
+
+impl Layout {
+    pub fn min_width(&self) -> usize {
+        self.min_width.unwrap_or(120)
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutMode {
+    /// Articles open full screen from the list
+    #[default]
+    Stacked,
+    /// The selected article is previewed next to the list on wide terminals
+    Panes,
+}
+
+/// `[export]` settings for saving articles to files.
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct Export {
+    /// Where articles are saved, defaults to `$XDG_DATA_HOME/news-rss/articles`
+    pub dir: Option<PathBuf>,
+    /// File name without extension, `{date}`, `{feed}`, `{title}` and `{id}`
+    /// are replaced, defaults to `{date}-{title}`
+    pub filename: Option<String>,
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Markdown with YAML front matter
+    #[default]
+    Markdown,
+    /// The article HTML with the metadata in `<meta>` tags
+    Html,
+}
+
+impl Export {
+    pub fn dir(&self) -> PathBuf {
+        match &self.dir {
+            Some(dir) => dir.clone(),
+            None => utils::get_data_dir().join("articles"),
+        }
+    }
+
+    pub fn filename(&self) -> &str {
+        self.filename.as_deref().unwrap_or("{date}-{title}")
+    }
+}
+
+/// A `[[rules]]` table. An article matches when all of the given conditions
+/// do, the regexes only need to match part of the text.
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct Rule {
+    /// Shown by the dry run, defaults to the position in the file
+    pub name: Option<String>,
+    /// Feed id or title
+    pub feed: Option<String>,
+    /// Category id or label
+    pub category: Option<String>,
+    pub title: Option<String>,
+    pub content: Option<String>,
+    pub author: Option<String>,
+    /// Only articles published more than this many days ago
+    pub older_than_days: Option<i64>,
+    #[serde(default)]
+    pub mark_read: bool,
+    #[serde(default)]
+    pub star: bool,
+    #[serde(default)]
+    pub hide: bool,
+    #[serde(default)]
+    pub highlight: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// A `[[highlights]]` table, e.g. `pattern = "CVE-\\d+-\\d+"` with `regex = true`.
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct Highlight {
+    pub pattern: String,
+    /// Treat `pattern` as a regex instead of literal text
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    /// Colour name like `red` or `light yellow`, or `#rrggbb`
+    pub color: Option<String>,
+    pub background: Option<String>,
+    #[serde(default)]
+    pub bold: bool,
+    #[serde(default)]
+    pub underline: bool,
+}
+
+/// A `[share.<name>]` table, the `type` key selects the kind of target.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ShareTarget {
+    /// Adds the article to a Wallabag instance through its API
+    Wallabag {
+        url: String,
+        client_id: String,
+        client_secret: String,
+        username: String,
+        password: String,
+        #[serde(default)]
+        tags: Vec<String>,
+    },
+    /// Any service speaking the Pocket `v3/add` API
+    Pocket {
+        #[serde(default = "default_pocket_url")]
+        url: String,
+        consumer_key: String,
+        access_token: String,
+    },
+    /// POSTs `body` to `url`, `{title}`, `{link}`, `{feed}`, `{author}`,
+    /// `{date}` and `{id}` are replaced by JSON escaped article fields
+    Webhook {
+        url: String,
+        body: Option<String>,
+        #[serde(default)]
+        headers: Vec<String>,
+    },
+    /// Runs `command` with `sh -c`, the article fields are passed in
+    /// `NEWS_RSS_TITLE`, `NEWS_RSS_LINK` and similar variables
+    Command { command: String },
+}
+
+fn default_pocket_url() -> String {
+    String::from("https://getpocket.com/v3/add")
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct ProfileConfig {
+    pub fresh_rss_api_url: Option<String>,
+    pub fresh_rss_api_user: Option<String>,
+    pub fresh_rss_api_password: Option<String>,
+}
+
+/// A resolved account: backend settings plus the location of its `news.db`.
+#[derive(Debug, Default, Clone)]
+pub struct Profile {
+    pub name: String,
+    pub fresh_rss_api_url: Option<String>,
+    pub fresh_rss_api_user: Option<String>,
+    pub fresh_rss_api_password: Option<String>,
+    pub data_dir: PathBuf,
+    pub db_path: PathBuf,
+}
+
+impl Profile {
+    fn new(name: String, settings: &ProfileConfig, data_dir: PathBuf) -> Self {
+        Self {
+            name,
+            fresh_rss_api_url: settings.fresh_rss_api_url.clone(),
+            fresh_rss_api_user: settings.fresh_rss_api_user.clone(),
+            fresh_rss_api_password: settings.fresh_rss_api_password.clone(),
+            db_path: data_dir.join("news.db"),
+            data_dir,
+        }
+    }
 }
 
 impl Config {
-    pub fn from(path: &String) -> Result<Config, Box<dyn Error>> {
+    pub fn from(path: &Path) -> Result<Config, Box<dyn Error>> {
         let contents = fs::read_to_string(path)?;
         let config: Config = toml::from_str(&contents)?;
 
         Ok(config)
     }
+
+    pub fn download_dir(&self) -> PathBuf {
+        match &self.download_dir {
+            Some(dir) => dir.clone(),
+            None => utils::get_download_dir(),
+        }
+    }
+
+    pub fn feed_settings(&self, feed_id: &str, feed_title: &str) -> FeedSettings {
+        self.feeds
+            .get(feed_id)
+            .or_else(|| self.feeds.get(feed_title))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn profile_names(&self) -> Vec<String> {
+        if self.profiles.is_empty() {
+            vec![DEFAULT_PROFILE.to_string()]
+        } else {
+            self.profiles.keys().cloned().collect()
+        }
+    }
+
+    /// Resolves the profile with the given name, falling back to
+    /// `default_profile` and then to the first profile in the file.
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile, Box<dyn Error>> {
+        if self.profiles.is_empty() {
+            if let Some(name) = name.filter(|n| *n != DEFAULT_PROFILE) {
+                return Err(format!("Profile `{}` is not defined in config.toml", name).into());
+            }
+            let settings = ProfileConfig {
+                fresh_rss_api_url: self.fresh_rss_api_url.clone(),
+                fresh_rss_api_user: self.fresh_rss_api_user.clone(),
+                fresh_rss_api_password: self.fresh_rss_api_password.clone(),
+            };
+            // Older versions kept the database next to config.toml, keep using it
+            let legacy_dir = utils::get_config_dir();
+            let data_dir = if legacy_dir.join("news.db").exists() {
+                legacy_dir
+            } else {
+                utils::get_data_dir()
+            };
+            return Ok(Profile::new(
+                DEFAULT_PROFILE.to_string(),
+                &settings,
+                data_dir,
+            ));
+        }
+
+        let name = match name.or(self.default_profile.as_deref()) {
+            Some(name) => name.to_string(),
+            None => self.profiles.keys().next().unwrap().clone(),
+        };
+        let profile = self
+            .profiles
+            .get(&name)
+            .ok_or(format!("Profile `{}` is not defined in config.toml", name))?;
+        let data_dir = utils::get_data_dir().join("profiles").join(&name);
+
+        Ok(Profile::new(name, profile, data_dir))
+    }
 }
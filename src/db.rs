@@ -1,24 +1,84 @@
 use crate::article::Article;
+use crate::config::Retention;
+use crate::enclosure::Enclosure;
 use crate::feed::Feed;
 use crate::greader::Category;
-use crate::utils;
-use rusqlite::{Connection, Result};
+use crate::rules::{Actions, RuleMatch, Rules, Target};
+use crate::utils::SECONDS_IN_DAY;
+use chrono::Local;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// Schema changes applied in order on top of the tables from `create_db`.
+/// The number of applied ones is kept in `PRAGMA user_version`, so only ever
+/// append to this list.
+const MIGRATIONS: &[&str] = &[
+    "
+    ALTER TABLE articles ADD COLUMN starred INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE articles ADD COLUMN read_at INTEGER;
+    ",
+    "ALTER TABLE articles ADD COLUMN full_content TEXT;",
+    "
+    ALTER TABLE articles ADD COLUMN hidden INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE articles ADD COLUMN highlighted INTEGER NOT NULL DEFAULT 0;
+    CREATE TABLE IF NOT EXISTS article_tags (
+        article_id  VARCHAR(1024) NOT NULL,
+        tag         VARCHAR(1024) NOT NULL,
+        PRIMARY KEY (article_id, tag)
+    );
+    ",
+    "
+    ALTER TABLE feeds ADD COLUMN icon_url VARCHAR(1024);
+    ALTER TABLE feeds ADD COLUMN categories VARCHAR(1024);
+    ALTER TABLE feeds ADD COLUMN last_error TEXT;
+    ",
+    "
+    CREATE TABLE IF NOT EXISTS read_events (
+        id          INTEGER PRIMARY KEY AUTOINCREMENT,
+        article_id  VARCHAR(1024) NOT NULL,
+        feed_id     VARCHAR(1024) NOT NULL,
+        kind        VARCHAR(16) NOT NULL,
+        duration    INTEGER,
+        created_at  INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_read_events_created_at ON read_events (created_at);
+    CREATE INDEX IF NOT EXISTS idx_read_events_feed_ids ON read_events (feed_id);
+    ",
+];
+
+/// `sync_state` row remembering when the database was last fully vacuumed.
+const VACUUM_STATE: &str = "local/vacuum";
 
 pub struct DB {
     conn: Connection,
+    rules: Rules,
 }
 
 impl DB {
-    pub fn new() -> Self {
-        let conn = Connection::open(format!("{}/news.db", utils::get_config_dir()))
-            .expect("Something went wrong while opening database.");
+    pub fn new(path: &Path) -> Self {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).expect("Something went wrong while creating data directory.");
+        }
+        let conn = Connection::open(path).expect("Something went wrong while opening database.");
+
+        Self {
+            conn,
+            rules: Rules::default(),
+        }
+    }
 
-        Self { conn }
+    /// Rules applied by `create_article` to articles it inserts.
+    pub fn with_rules(mut self, rules: Rules) -> Self {
+        self.rules = rules;
+        self
     }
 
     pub fn create_db(&self) -> Result<()> {
         self.conn.execute_batch(
             "
+            PRAGMA auto_vacuum = INCREMENTAL;
             BEGIN;
             CREATE TABLE IF NOT EXISTS unread_articles (
                 id  VARCHAR(1024) PRIMARY KEY
@@ -59,36 +119,115 @@ impl DB {
             CREATE INDEX IF NOT EXISTS idx_articles_feed_ids ON articles (feed_id);
             CREATE INDEX IF NOT EXISTS idx_articles_short_ids ON articles (short_id);
 
+            CREATE TABLE IF NOT EXISTS sync_state (
+                stream_id       VARCHAR(1024) PRIMARY KEY,
+                last_synced     INTEGER,
+                continuation    VARCHAR(1024),
+                server_updated  INTEGER
+            );
+
+            CREATE TABLE IF NOT EXISTS enclosures (
+                article_id  VARCHAR(1024) NOT NULL,
+                url         VARCHAR(1024) NOT NULL,
+                mime_type   VARCHAR(255),
+                length      INTEGER,
+                PRIMARY KEY (article_id, url)
+            );
+
             COMMIT;
         ",
         )?;
+        self.migrate()?;
+        self.enable_incremental_vacuum()
+    }
+
+    /// Setting `auto_vacuum` only takes effect on a database that already has
+    /// tables after a full `VACUUM`, which is run once to switch it over.
+    fn enable_incremental_vacuum(&self) -> Result<()> {
+        let mode: i64 = self
+            .conn
+            .query_row("PRAGMA auto_vacuum", [], |row| row.get(0))?;
+        // 2 is INCREMENTAL
+        if mode != 2 {
+            self.conn
+                .execute_batch("PRAGMA auto_vacuum = INCREMENTAL; VACUUM;")?;
+        }
+        Ok(())
+    }
+
+    fn migrate(&self) -> Result<()> {
+        let version: i64 = self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            self.conn.execute_batch(&format!(
+                "BEGIN; {} PRAGMA user_version = {}; COMMIT;",
+                migration,
+                index + 1
+            ))?;
+        }
         Ok(())
     }
 
+    /// Inserts the feed or refreshes the metadata of a stored one. A feed
+    /// subscribed to again comes back with a new id for the same URL, which
+    /// takes over the old row and its articles.
     pub fn create_feed(&self, params: CreateFeedParams) -> Result<()> {
-        self.conn.execute(
-            "INSERT OR IGNORE INTO feeds (
+        let tx = self.conn.unchecked_transaction()?;
+        let previous: Option<String> = tx
+            .query_row(
+                "SELECT id FROM feeds WHERE rss_link = ?1 AND id != ?2",
+                [&params.rss_link, &params.id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if let Some(previous) = previous {
+            tx.execute("DELETE FROM feeds WHERE id = ?1", [&previous])?;
+            tx.execute(
+                "UPDATE articles SET feed_id = ?2 WHERE feed_id = ?1",
+                [&previous, &params.id],
+            )?;
+            tx.execute(
+                "UPDATE read_events SET feed_id = ?2 WHERE feed_id = ?1",
+                [&previous, &params.id],
+            )?;
+        }
+        tx.execute(
+            "INSERT INTO feeds (
                 title       ,
                 rss_link    ,
                 link        ,
                 description ,
                 pub_date    ,
                 category_id,
-                id
+                id          ,
+                icon_url    ,
+                categories  ,
+                last_error
             ) values (
-                ?1, ?2, ?3, ?4, ?5, ?6, ?7
-            )",
-            [
+                ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10
+            )
+            ON CONFLICT (id) DO UPDATE SET
+                title = excluded.title,
+                link = excluded.link,
+                category_id = excluded.category_id,
+                icon_url = excluded.icon_url,
+                categories = excluded.categories,
+                last_error = excluded.last_error",
+            params![
                 params.title,
                 params.rss_link,
                 params.link,
                 params.description,
-                String::from(""),
+                "",
                 params.category_id,
                 params.id,
+                params.icon_url,
+                params.categories,
+                params.last_error,
             ],
         )?;
-        Ok(())
+        tx.commit()
     }
 
     pub fn create_category(&self, params: CreateCategoryParams) -> Result<()> {
@@ -135,7 +274,7 @@ impl DB {
                 FROM articles a
                 INNER JOIN feeds f ON
                     a.feed_id  = f.id
-                WHERE f.id = :feed_id AND a.unread = 1",
+                WHERE f.id = :feed_id AND a.unread = 1 AND a.hidden = 0",
         )?;
         let count = stmt.query_row(&[(":feed_id", feed_id)], |row| {
             Ok(UnreadCount { count: row.get(0)? })
@@ -144,6 +283,14 @@ impl DB {
         Ok(count.count)
     }
 
+    pub fn get_total_unread_count(&self) -> Result<i64> {
+        self.conn.query_row(
+            "SELECT COUNT(*) FROM articles WHERE unread = 1 AND hidden = 0",
+            [],
+            |row| row.get(0),
+        )
+    }
+
     pub fn get_category_unread_count(&self, category_id: &str) -> Result<i64> {
         let mut stmt = self.conn.prepare(
             "
@@ -151,7 +298,7 @@ impl DB {
                 FROM articles a
                 INNER JOIN feeds f ON a.feed_id = f.id
                 INNER JOIN categories c ON f.category_id = c.id
-                WHERE c.id = :category_id AND a.unread = 1",
+                WHERE c.id = :category_id AND a.unread = 1 AND a.hidden = 0",
         )?;
         let count = stmt.query_row(&[(":category_id", category_id)], |row| {
             Ok(UnreadCount { count: row.get(0)? })
@@ -160,12 +307,15 @@ impl DB {
         Ok(count.count)
     }
 
-    pub fn create_article(&self, params: CreateArticleParams) -> Result<()> {
+    /// Inserts the article unless it's already stored and applies the rules
+    /// to new ones, returning what they matched.
+    pub fn create_article(&self, params: CreateArticleParams) -> Result<Option<RuleMatch>> {
         let parts: Vec<&str> = params.id.split("/").collect();
         // See: https://github.com/bazqux/bazqux-api#about-item-ids
         // See: https://github.com/FreshRSS/FreshRSS/blob/edge/p/api/greader.php#L37-L39
         let short_id = i64::from_str_radix(parts.last().unwrap(), 16).unwrap();
-        self.conn.execute(
+        let article_id = params.id.clone();
+        let inserted = self.conn.execute(
             "INSERT OR IGNORE INTO articles (
                 id ,
                 short_id ,
@@ -176,9 +326,10 @@ impl DB {
                 unread     ,
                 feed_id    ,
                 pub_date   ,
-                author
+                author     ,
+                starred
             ) values (
-                ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10
+                ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11
             )",
             [
                 params.id,
@@ -191,11 +342,157 @@ impl DB {
                 params.feed_id,
                 params.pub_date.to_string(),
                 params.author.unwrap_or("".to_string()),
+                String::from(if params.starred { "1" } else { "0" }),
+            ],
+        )?;
+        if inserted == 0 || self.rules.is_empty() {
+            return Ok(None);
+        }
+
+        let now = Local::now().timestamp();
+        let rule_match = self
+            .get_rule_targets(Some(&article_id))?
+            .into_iter()
+            .next()
+            .and_then(|target| self.rules.apply(target, now));
+        if let Some(rule_match) = &rule_match {
+            self.apply_actions(&article_id, &rule_match.actions)?;
+        }
+        Ok(rule_match)
+    }
+
+    /// Articles as seen by the rules, a single one or all that aren't hidden.
+    fn get_rule_targets(&self, article_id: Option<&str>) -> Result<Vec<Target>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT
+                a.id,
+                a.title,
+                a.content,
+                a.author,
+                a.pub_date,
+                a.feed_id,
+                COALESCE(f.title, ''),
+                COALESCE(c.id, ''),
+                COALESCE(c.label, ''),
+                a.unread,
+                a.starred
+            FROM articles a
+            LEFT JOIN feeds f ON a.feed_id = f.id
+            LEFT JOIN categories c ON f.category_id = c.id
+            WHERE (?1 IS NULL AND a.hidden = 0) OR a.id = ?1",
+        )?;
+        let targets = stmt.query_map([article_id], |row| {
+            Ok(Target {
+                article_id: row.get(0)?,
+                title: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                content: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                author: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+                pub_date: row.get::<_, Option<i64>>(4)?.unwrap_or_default(),
+                feed_id: row.get(5)?,
+                feed_title: row.get(6)?,
+                category_id: row.get(7)?,
+                category_label: row.get(8)?,
+                unread: row.get(9)?,
+                starred: row.get(10)?,
+            })
+        })?;
+        targets.collect()
+    }
+
+    fn apply_actions(&self, article_id: &str, actions: &Actions) -> Result<()> {
+        if actions.mark_read {
+            self.conn.execute(
+                "UPDATE
+                    articles
+                SET
+                    unread = 0,
+                    read_at = strftime('%s', 'now')
+                WHERE id = ?1 AND unread = 1",
+                [article_id],
+            )?;
+        }
+        self.conn.execute(
+            "UPDATE
+                articles
+            SET
+                starred = starred OR ?2,
+                hidden = hidden OR ?3,
+                highlighted = highlighted OR ?4
+            WHERE id = ?1",
+            params![article_id, actions.star, actions.hide, actions.highlight],
+        )?;
+        for tag in &actions.tags {
+            self.add_article_tag(article_id, tag)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the rules over every article that isn't hidden yet. With
+    /// `dry_run` the matches are only reported.
+    pub fn apply_rules(&self, dry_run: bool) -> Result<Vec<RuleMatch>> {
+        let now = Local::now().timestamp();
+        let matches: Vec<RuleMatch> = self
+            .get_rule_targets(None)?
+            .into_iter()
+            .filter_map(|target| self.rules.apply(target, now))
+            .collect();
+        if !dry_run {
+            let tx = self.conn.unchecked_transaction()?;
+            for rule_match in &matches {
+                self.apply_actions(&rule_match.target.article_id, &rule_match.actions)?;
+            }
+            tx.commit()?;
+        }
+        Ok(matches)
+    }
+
+    pub fn create_enclosure(&self, enclosure: &Enclosure) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO enclosures (
+                article_id,
+                url       ,
+                mime_type ,
+                length
+            ) values (
+                ?1, ?2, ?3, ?4
+            )",
+            params![
+                enclosure.article_id,
+                enclosure.url,
+                enclosure.mime_type,
+                enclosure.length,
             ],
         )?;
         Ok(())
     }
 
+    pub fn get_enclosures(&self, article_id: &str) -> Result<Vec<Enclosure>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT
+                article_id,
+                url       ,
+                mime_type ,
+                length
+            FROM
+                enclosures
+            WHERE
+                article_id = :article_id",
+        )?;
+        let enclosure_iter = stmt.query_map(&[(":article_id", article_id)], |row| {
+            Ok(Enclosure::new(
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+            ))
+        })?;
+        let mut enclosures = Vec::new();
+        for enclosure in enclosure_iter {
+            enclosures.push(enclosure?);
+        }
+        Ok(enclosures)
+    }
+
     /* pub fn get_all_articles(&self) -> Result<Vec<Article>> {
         let mut stmt = self.conn.prepare(
             "
@@ -245,11 +542,12 @@ impl DB {
                 content     ,
                 unread      ,
                 feed_id     ,
-                pub_date
+                pub_date    ,
+                highlighted
             FROM
                 articles
             WHERE
-                feed_id = :feed_id AND unread = 1
+                feed_id = :feed_id AND unread = 1 AND hidden = 0
             ORDER BY pub_date DESC",
         )?;
 
@@ -264,6 +562,7 @@ impl DB {
                 unread,
                 row.get(6)?,
                 row.get(7)?,
+                row.get(8)?,
             ))
         })?;
         let mut articles = Vec::new();
@@ -283,12 +582,13 @@ impl DB {
                 a.content     ,
                 a.unread      ,
                 a.feed_id     ,
-                a.pub_date
+                a.pub_date    ,
+                a.highlighted
             FROM
                 articles a
             INNER JOIN feeds f ON a.feed_id = f.id
             INNER JOIN categories c ON f.category_id = c.id
-            WHERE c.id = :category_id AND a.unread = 1
+            WHERE c.id = :category_id AND a.unread = 1 AND a.hidden = 0
             ORDER BY a.pub_date DESC",
         )?;
 
@@ -303,6 +603,139 @@ impl DB {
                 unread,
                 row.get(6)?,
                 row.get(7)?,
+                row.get(8)?,
+            ))
+        })?;
+        let mut articles = Vec::new();
+        for article in article_iter {
+            articles.push(article?);
+        }
+        Ok(articles)
+    }
+
+    /// Articles whose title or content contains `query`, newest first. The
+    /// content is matched as HTML, so callers check its text themselves.
+    pub fn search_articles(&self, query: &str) -> Result<Vec<Article>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT
+                id      ,
+                link    ,
+                title       ,
+                description ,
+                content     ,
+                unread      ,
+                feed_id     ,
+                pub_date    ,
+                highlighted
+            FROM
+                articles
+            WHERE
+                hidden = 0 AND (
+                    title LIKE :query ESCAPE '\\' OR content LIKE :query ESCAPE '\\'
+                )
+            ORDER BY pub_date DESC",
+        )?;
+
+        let pattern = format!(
+            "%{}%",
+            query
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+        let article_iter = stmt.query_map(&[(":query", &pattern)], |row| {
+            let unread: i8 = row.get(5).unwrap();
+            Ok(Article::new(
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                unread,
+                row.get(6)?,
+                row.get(7)?,
+                row.get(8)?,
+            ))
+        })?;
+        let mut articles = Vec::new();
+        for article in article_iter {
+            articles.push(article?);
+        }
+        Ok(articles)
+    }
+
+    pub fn get_unread_articles(&self) -> Result<Vec<Article>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT
+                id      ,
+                link    ,
+                title       ,
+                description ,
+                content     ,
+                unread      ,
+                feed_id     ,
+                pub_date    ,
+                highlighted
+            FROM
+                articles
+            WHERE
+                unread = 1 AND hidden = 0
+            ORDER BY pub_date DESC",
+        )?;
+
+        let article_iter = stmt.query_map([], |row| {
+            let unread: i8 = row.get(5).unwrap();
+            Ok(Article::new(
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                unread,
+                row.get(6)?,
+                row.get(7)?,
+                row.get(8)?,
+            ))
+        })?;
+        let mut articles = Vec::new();
+        for article in article_iter {
+            articles.push(article?);
+        }
+        Ok(articles)
+    }
+
+    /// Tagged articles that aren't hidden, read ones included.
+    pub fn get_articles_for_tag(&self, tag: &str) -> Result<Vec<Article>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT
+                a.id      ,
+                a.link    ,
+                a.title       ,
+                a.description ,
+                a.content     ,
+                a.unread      ,
+                a.feed_id     ,
+                a.pub_date    ,
+                a.highlighted
+            FROM
+                articles a
+            INNER JOIN article_tags t ON a.id = t.article_id
+            WHERE t.tag = :tag AND a.hidden = 0
+            ORDER BY a.pub_date DESC",
+        )?;
+
+        let article_iter = stmt.query_map(&[(":tag", tag)], |row| {
+            let unread: i8 = row.get(5).unwrap();
+            Ok(Article::new(
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                unread,
+                row.get(6)?,
+                row.get(7)?,
+                row.get(8)?,
             ))
         })?;
         let mut articles = Vec::new();
@@ -312,6 +745,78 @@ impl DB {
         Ok(articles)
     }
 
+    /// Every tag with the number of its unread articles.
+    pub fn get_tags(&self) -> Result<Vec<(String, i64)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT
+                t.tag,
+                COUNT(CASE WHEN a.unread = 1 AND a.hidden = 0 THEN 1 END)
+            FROM article_tags t
+            INNER JOIN articles a ON a.id = t.article_id
+            GROUP BY t.tag
+            ORDER BY t.tag",
+        )?;
+        let tags = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        tags.collect()
+    }
+
+    pub fn get_article_tags(&self, article_id: &str) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT tag FROM article_tags WHERE article_id = ?1 ORDER BY tag")?;
+        let tags = stmt.query_map([article_id], |row| row.get(0))?;
+        tags.collect()
+    }
+
+    pub fn add_article_tag(&self, article_id: &str, tag: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO article_tags (article_id, tag) values (?1, ?2)",
+            [article_id, tag],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_article_tag(&self, article_id: &str, tag: &str) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM article_tags WHERE article_id = ?1 AND tag = ?2",
+            [article_id, tag],
+        )?;
+        Ok(())
+    }
+
+    /// Makes the local tags match the server: `tags` maps each tag to the
+    /// short ids of its items, tags missing from it are dropped.
+    pub fn replace_tags(&self, tags: &[(String, Vec<String>)]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM article_tags", ())?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR IGNORE INTO article_tags (article_id, tag)
+                SELECT id, ?2 FROM articles WHERE short_id = ?1",
+            )?;
+            for (tag, short_ids) in tags {
+                for short_id in short_ids {
+                    stmt.execute([short_id, tag])?;
+                }
+            }
+        }
+        tx.commit()
+    }
+
+    /// Makes the starred flags match the server's starred stream, given as
+    /// short ids.
+    pub fn replace_starred(&self, short_ids: &[String]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("UPDATE articles SET starred = 0 WHERE starred = 1", ())?;
+        {
+            let mut stmt = tx.prepare("UPDATE articles SET starred = 1 WHERE short_id = ?1")?;
+            for short_id in short_ids {
+                stmt.execute([short_id])?;
+            }
+        }
+        tx.commit()
+    }
+
     /* pub fn get_articles(&self, rss_link: &str) -> Result<Vec<Article>> {
         let mut stmt = self.conn.prepare(
             "
@@ -365,7 +870,8 @@ impl DB {
                 content     ,
                 unread      ,
                 feed_id     ,
-                pub_date
+                pub_date    ,
+                highlighted
             FROM
                 articles
             WHERE
@@ -383,11 +889,20 @@ impl DB {
                 unread,
                 row.get(6)?,
                 row.get(7)?,
+                row.get(8)?,
             ))
         })?;
         Ok(article)
     }
 
+    pub fn get_starred_article_ids(&self) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id FROM articles WHERE starred = 1 ORDER BY pub_date DESC")?;
+        let ids = stmt.query_map([], |row| row.get(0))?;
+        ids.collect()
+    }
+
     pub fn get_feeds_for_category(&self, category_id: &str) -> Result<Vec<Feed>> {
         let mut stmt = self.conn.prepare(
             "
@@ -461,7 +976,8 @@ impl DB {
             "UPDATE
                 articles
             SET
-                unread = 0
+                unread = 0,
+                read_at = strftime('%s', 'now')
             WHERE
                 id = ?",
         )?;
@@ -474,7 +990,8 @@ impl DB {
             "UPDATE
                 articles
             SET
-                unread = 1
+                unread = 1,
+                read_at = NULL
             WHERE
                 id = ?",
         )?;
@@ -482,15 +999,76 @@ impl DB {
         Ok(())
     }
 
-    pub fn add_unread_id(&self, id: &str) -> Result<()> {
+    /// Remembers that the article was read, for the reading stats. Events
+    /// are local only and survive resyncs and pruning.
+    pub fn record_read_event(
+        &self,
+        article_id: &str,
+        event: ReadEvent,
+        duration: Option<i64>,
+    ) -> Result<()> {
         self.conn.execute(
-            "INSERT INTO unread_articles (
-                id
-            ) values (
-                ?1
-            )",
-            [id],
+            "INSERT INTO read_events (article_id, feed_id, kind, duration, created_at)
+            SELECT id, feed_id, ?2, ?3, strftime('%s', 'now') FROM articles WHERE id = ?1",
+            params![article_id, event.as_str(), duration],
+        )?;
+        Ok(())
+    }
+
+    /// Number of distinct articles read per period since `since`, where the
+    /// period is a `strftime` format such as `%Y-%m-%d`.
+    pub fn get_reads_per_period(&self, format: &str, since: i64) -> Result<Vec<(String, i64)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT
+                strftime(?1, created_at, 'unixepoch', 'localtime') AS period,
+                COUNT(DISTINCT article_id)
+            FROM read_events
+            WHERE created_at >= ?2
+            GROUP BY period
+            ORDER BY period",
+        )?;
+        let rows = stmt.query_map(params![format, since], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    }
+
+    pub fn get_feed_read_stats(&self) -> Result<Vec<FeedReadStats>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT
+                f.id,
+                COALESCE(f.title, ''),
+                (SELECT COUNT(*) FROM articles a WHERE a.feed_id = f.id),
+                (SELECT COUNT(DISTINCT e.article_id) FROM read_events e
+                    JOIN articles a ON a.id = e.article_id
+                    WHERE e.feed_id = f.id),
+                (SELECT COALESCE(SUM(e.duration), 0) FROM read_events e
+                    WHERE e.feed_id = f.id),
+                (SELECT MAX(a.pub_date) FROM articles a WHERE a.feed_id = f.id)
+            FROM feeds f
+            ORDER BY f.title COLLATE NOCASE",
         )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(FeedReadStats {
+                feed_id: row.get(0)?,
+                title: row.get(1)?,
+                articles: row.get(2)?,
+                read: row.get(3)?,
+                reading_seconds: row.get(4)?,
+                last_post: row.get(5)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    pub fn set_article_full_content(&self, article_id: &str, content: &str) -> Result<()> {
+        let mut stmt = self.conn.prepare(
+            "UPDATE
+                articles
+            SET
+                full_content = ?1
+            WHERE
+                id = ?2",
+        )?;
+        stmt.execute([content, article_id])?;
         Ok(())
     }
 
@@ -499,22 +1077,238 @@ impl DB {
         Ok(())
     }
 
+    /// Replaces the local read state with the server's list of unread short
+    /// ids, in both directions.
     pub fn mark_articles_as_read_except(&self, article_ids: Vec<String>) -> Result<()> {
-        self.clear_unread_articles()?;
-        for id in article_ids {
-            self.add_unread_id(&id)?
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM unread_articles", ())?;
+        {
+            let mut stmt = tx.prepare("INSERT OR IGNORE INTO unread_articles (id) values (?1)")?;
+            for id in article_ids {
+                stmt.execute([id])?;
+            }
         }
-        let mut stmt = self.conn.prepare(
+        tx.execute(
             "UPDATE
                 articles
             SET
-                unread = 0
+                unread = 0,
+                read_at = strftime('%s', 'now')
             WHERE unread != 0 AND short_id NOT IN (SELECT id FROM unread_articles)",
+            (),
+        )?;
+        tx.execute(
+            "UPDATE
+                articles
+            SET
+                unread = 1,
+                read_at = NULL
+            WHERE unread = 0 AND short_id IN (SELECT id FROM unread_articles)",
+            (),
+        )?;
+        tx.commit()
+    }
+
+    /// Short ids from the last `mark_articles_as_read_except` call which
+    /// aren't in the database yet.
+    pub fn get_missing_unread_ids(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT
+                id
+            FROM
+                unread_articles
+            WHERE id NOT IN (SELECT short_id FROM articles)",
+        )?;
+        let ids_iter = stmt.query_map([], |row| row.get(0))?;
+        let mut ids = Vec::new();
+        for id in ids_iter {
+            ids.push(id?);
+        }
+        Ok(ids)
+    }
+
+    pub fn get_sync_state(&self, stream_id: &str) -> Result<SyncState> {
+        let mut stmt = self.conn.prepare(
+            "
+            SELECT
+                stream_id,
+                last_synced,
+                continuation,
+                server_updated
+            FROM
+                sync_state
+            WHERE
+                stream_id = :stream_id",
+        )?;
+        let state = stmt.query_row(&[(":stream_id", stream_id)], |row| {
+            Ok(SyncState {
+                stream_id: row.get(0)?,
+                last_synced: row.get(1)?,
+                continuation: row.get(2)?,
+                server_updated: row.get(3)?,
+            })
+        });
+        match state {
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(SyncState {
+                stream_id: stream_id.to_string(),
+                ..SyncState::default()
+            }),
+            state => state,
+        }
+    }
+
+    pub fn save_sync_state(&self, state: &SyncState) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO sync_state (
+                stream_id,
+                last_synced,
+                continuation,
+                server_updated
+            ) values (
+                ?1, ?2, ?3, ?4
+            )",
+            params![
+                state.stream_id,
+                state.last_synced,
+                state.continuation,
+                state.server_updated,
+            ],
         )?;
-        stmt.execute([])?;
         Ok(())
     }
 
+    /// Forgets everything fetched from the server, the next sync starts from
+    /// scratch.
+    pub fn reset_local_cache(&self) -> Result<()> {
+        self.conn.execute_batch(
+            "
+            BEGIN;
+            DELETE FROM sync_state;
+            DELETE FROM unread_articles;
+            DELETE FROM articles;
+            DELETE FROM enclosures;
+            DELETE FROM article_tags;
+            DELETE FROM feeds;
+            DELETE FROM categories;
+            COMMIT;
+        ",
+        )?;
+        Ok(())
+    }
+
+    /// Deletes articles that fall out of the retention settings and gives the
+    /// space back to the file system. Unread and starred articles are kept.
+    pub fn prune(&self, retention: &Retention) -> Result<PruneReport> {
+        let size_before = self.database_size()?;
+        let now = Local::now().timestamp();
+        let mut articles = 0;
+
+        if let Some(days) = retention.read_days {
+            articles += self.conn.execute(
+                "DELETE FROM
+                    articles
+                WHERE
+                    unread = 0 AND starred = 0 AND COALESCE(read_at, pub_date) < ?1",
+                [now - days * SECONDS_IN_DAY],
+            )?;
+        }
+
+        if let Some(max) = retention.max_per_feed {
+            articles += self.conn.execute(
+                "DELETE FROM
+                    articles
+                WHERE id IN (
+                    SELECT id FROM (
+                        SELECT
+                            id,
+                            unread,
+                            starred,
+                            ROW_NUMBER() OVER (PARTITION BY feed_id ORDER BY pub_date DESC) AS n
+                        FROM articles
+                    )
+                    WHERE n > ?1 AND unread = 0 AND starred = 0
+                )",
+                [max],
+            )?;
+        }
+
+        self.conn.execute(
+            "DELETE FROM enclosures WHERE article_id NOT IN (SELECT id FROM articles)",
+            (),
+        )?;
+        self.conn.execute(
+            "DELETE FROM article_tags WHERE article_id NOT IN (SELECT id FROM articles)",
+            (),
+        )?;
+
+        let state = self.get_sync_state(VACUUM_STATE)?;
+        let vacuum_due = match (retention.vacuum_interval_days, state.last_synced) {
+            (Some(days), Some(last)) => last + days * SECONDS_IN_DAY <= now,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        if vacuum_due {
+            self.conn.execute_batch("VACUUM")?;
+            self.save_sync_state(&SyncState {
+                stream_id: VACUUM_STATE.to_string(),
+                last_synced: Some(now),
+                ..SyncState::default()
+            })?;
+        } else {
+            self.conn.execute_batch("PRAGMA incremental_vacuum")?;
+        }
+
+        Ok(PruneReport {
+            articles,
+            bytes: size_before - self.database_size()?,
+        })
+    }
+
+    fn database_size(&self) -> Result<i64> {
+        self.conn.query_row(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+            [],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn get_feed_info(&self, feed_id: &str) -> Result<FeedInfo> {
+        self.conn.query_row(
+            "SELECT
+                COALESCE(f.title, ''),
+                f.link,
+                f.rss_link,
+                f.icon_url,
+                COALESCE(f.categories, ''),
+                f.last_error,
+                COUNT(a.id),
+                COUNT(CASE WHEN a.unread = 1 THEN 1 END),
+                COUNT(CASE WHEN a.starred = 1 THEN 1 END),
+                MIN(a.pub_date),
+                MAX(a.pub_date)
+            FROM feeds f
+            LEFT JOIN articles a ON a.feed_id = f.id
+            WHERE f.id = ?1
+            GROUP BY f.id",
+            [feed_id],
+            |row| {
+                Ok(FeedInfo {
+                    title: row.get(0)?,
+                    link: row.get(1)?,
+                    rss_link: row.get(2)?,
+                    icon_url: row.get(3)?,
+                    categories: row.get(4)?,
+                    last_error: row.get(5)?,
+                    articles: row.get(6)?,
+                    unread: row.get(7)?,
+                    starred: row.get(8)?,
+                    first_item: row.get(9)?,
+                    last_item: row.get(10)?,
+                })
+            },
+        )
+    }
+
     pub fn get_article_details(&self, article_id: &str) -> Result<ArticleDetails> {
         let mut stmt = self.conn.prepare(
             // TODO: get also content
@@ -524,7 +1318,10 @@ impl DB {
                     a.link,
                     a.author,
                     a.pub_date,
-                    f.title AS feed_title
+                    f.title AS feed_title,
+                    f.link AS feed_link,
+                    a.full_content,
+                    a.starred
                 FROM articles a
                 INNER JOIN feeds f ON
                     a.feed_id = f.id
@@ -537,6 +1334,9 @@ impl DB {
                 author: row.get(2)?,
                 pub_date: row.get(3)?,
                 feed_title: row.get(4)?,
+                feed_link: row.get(5)?,
+                full_content: row.get(6)?,
+                starred: row.get(7)?,
             })
         })?;
         Ok(article_details)
@@ -551,6 +1351,10 @@ pub struct CreateFeedParams {
     pub description: String,
     pub pub_date: Option<String>,
     pub category_id: String,
+    pub icon_url: Option<String>,
+    /// Labels of all the feed's categories, comma separated
+    pub categories: String,
+    pub last_error: Option<String>,
 }
 
 pub struct CreateCategoryParams {
@@ -568,16 +1372,286 @@ pub struct CreateArticleParams {
     pub feed_id: String,
     pub pub_date: i64,
     pub author: Option<String>,
+    pub starred: bool,
 }
 
 pub struct UnreadCount {
     count: i64,
 }
 
+#[derive(Debug, Default, Clone)]
+pub struct SyncState {
+    pub stream_id: String,
+    pub last_synced: Option<i64>,
+    pub continuation: Option<String>,
+    pub server_updated: Option<i64>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct PruneReport {
+    pub articles: usize,
+    pub bytes: i64,
+}
+
+/// How an article was read, stored in `read_events.kind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadEvent {
+    /// Marked read from the article list
+    Read,
+    /// Opened in the browser
+    Browser,
+    /// Time spent in the article view, with its duration
+    Reader,
+}
+
+impl ReadEvent {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ReadEvent::Read => "read",
+            ReadEvent::Browser => "browser",
+            ReadEvent::Reader => "reader",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedReadStats {
+    pub feed_id: String,
+    pub title: String,
+    /// Stored articles
+    pub articles: i64,
+    /// Stored articles with at least one read event
+    pub read: i64,
+    pub reading_seconds: i64,
+    pub last_post: Option<i64>,
+}
+
+impl FeedReadStats {
+    pub fn read_ratio(&self) -> f64 {
+        if self.articles == 0 {
+            0.0
+        } else {
+            self.read as f64 / self.articles as f64
+        }
+    }
+}
+
+pub struct FeedInfo {
+    pub title: String,
+    pub link: String,
+    pub rss_link: String,
+    pub icon_url: Option<String>,
+    pub categories: String,
+    pub last_error: Option<String>,
+    pub articles: i64,
+    pub unread: i64,
+    pub starred: i64,
+    pub first_item: Option<i64>,
+    pub last_item: Option<i64>,
+}
+
+impl FeedInfo {
+    /// Average seconds between two stored articles.
+    pub fn posting_interval(&self) -> Option<i64> {
+        match (self.first_item, self.last_item) {
+            (Some(first), Some(last)) if self.articles > 1 => {
+                Some((last - first) / (self.articles - 1))
+            }
+            _ => None,
+        }
+    }
+}
+
 pub struct ArticleDetails {
     pub title: String,
     pub link: String,
     pub author: String,
     pub pub_date: i64,
     pub feed_title: String,
+    pub feed_link: String,
+    pub full_content: Option<String>,
+    pub starred: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Database in a file of its own, removed once the test is done.
+    struct TempDb {
+        db: DB,
+        path: PathBuf,
+    }
+
+    impl TempDb {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "news-rss-test-{}-{}.db",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_file(&path);
+            let db = DB::new(&path);
+            db.create_db().unwrap();
+            Self { db, path }
+        }
+
+        /// Adds an article, `short_id` being the hex last segment of its id.
+        fn article(&self, short_id: &str, pub_date: i64, unread: bool, starred: bool) -> String {
+            let id = format!("tag:google.com,2005:reader/item/{}", short_id);
+            self.db
+                .create_article(CreateArticleParams {
+                    id: id.clone(),
+                    link: String::new(),
+                    title: String::new(),
+                    description: String::new(),
+                    content: String::new(),
+                    unread: unread as i8,
+                    feed_id: String::from("feed"),
+                    pub_date,
+                    author: None,
+                    starred,
+                })
+                .unwrap();
+            // Articles are stored unread until the sync says otherwise
+            if !unread {
+                self.db.mark_article_as_read(&id).unwrap();
+            }
+            id
+        }
+
+        fn set_read_at(&self, id: &str, read_at: i64) {
+            self.db
+                .conn
+                .execute(
+                    "UPDATE articles SET read_at = ?1 WHERE id = ?2",
+                    params![read_at, id],
+                )
+                .unwrap();
+        }
+
+        fn ids(&self, sql: &str) -> Vec<String> {
+            let mut stmt = self.db.conn.prepare(sql).unwrap();
+            let ids = stmt.query_map([], |row| row.get(0)).unwrap();
+            ids.collect::<Result<_>>().unwrap()
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    #[test]
+    fn prune_keeps_unread_and_starred_articles_past_read_days() {
+        let temp = TempDb::new("prune-read-days");
+        let now = Local::now().timestamp();
+        let old = now - 40 * SECONDS_IN_DAY;
+        let read = temp.article("1", old, false, false);
+        let unread = temp.article("2", old, true, false);
+        let starred = temp.article("3", old, false, true);
+        let recently_read = temp.article("4", old, false, false);
+        temp.set_read_at(&read, old);
+        temp.set_read_at(&starred, old);
+        temp.set_read_at(&recently_read, now);
+
+        let report = temp
+            .db
+            .prune(&Retention {
+                read_days: Some(30),
+                ..Retention::default()
+            })
+            .unwrap();
+
+        assert_eq!(report.articles, 1);
+        assert_eq!(
+            temp.ids("SELECT id FROM articles ORDER BY short_id"),
+            vec![unread, starred, recently_read]
+        );
+    }
+
+    #[test]
+    fn prune_keeps_unread_and_starred_articles_over_max_per_feed() {
+        let temp = TempDb::new("prune-max-per-feed");
+        let newest = temp.article("5", 500, false, false);
+        let newer = temp.article("4", 400, false, false);
+        let unread = temp.article("3", 300, true, false);
+        let starred = temp.article("2", 200, false, true);
+        temp.article("1", 100, false, false);
+
+        let report = temp
+            .db
+            .prune(&Retention {
+                max_per_feed: Some(2),
+                ..Retention::default()
+            })
+            .unwrap();
+
+        assert_eq!(report.articles, 1);
+        assert_eq!(
+            temp.ids("SELECT id FROM articles ORDER BY pub_date DESC"),
+            vec![newest, newer, unread, starred]
+        );
+    }
+
+    #[test]
+    fn prune_removes_enclosures_and_tags_of_deleted_articles() {
+        let temp = TempDb::new("prune-orphans");
+        let kept = temp.article("1", 200, false, false);
+        let pruned = temp.article("2", 100, false, false);
+        for id in [&kept, &pruned] {
+            temp.db
+                .create_enclosure(&Enclosure::new(
+                    id.clone(),
+                    format!("https://example.com/{}.mp3", id),
+                    None,
+                    None,
+                ))
+                .unwrap();
+            temp.db.add_article_tag(id, "podcast").unwrap();
+        }
+
+        temp.db
+            .prune(&Retention {
+                max_per_feed: Some(1),
+                ..Retention::default()
+            })
+            .unwrap();
+
+        assert_eq!(
+            temp.ids("SELECT article_id FROM enclosures"),
+            vec![kept.clone()]
+        );
+        assert_eq!(temp.ids("SELECT article_id FROM article_tags"), vec![kept]);
+    }
+
+    #[test]
+    fn mark_articles_as_read_except_follows_the_server() {
+        let temp = TempDb::new("read-state");
+        let read_on_server = temp.article("a", 100, true, false);
+        let unread_on_server = temp.article("b", 100, false, false);
+        let still_unread = temp.article("c", 100, true, false);
+        temp.set_read_at(&unread_on_server, 100);
+
+        // Short ids come from the server in decimal, 0xd isn't stored yet
+        temp.db
+            .mark_articles_as_read_except(vec![
+                String::from("11"),
+                String::from("12"),
+                String::from("13"),
+            ])
+            .unwrap();
+
+        assert_eq!(
+            temp.ids("SELECT id FROM articles WHERE unread = 1 ORDER BY short_id"),
+            vec![unread_on_server.clone(), still_unread]
+        );
+        assert_eq!(
+            temp.ids("SELECT id FROM articles WHERE read_at IS NOT NULL"),
+            vec![read_on_server]
+        );
+        assert_eq!(temp.db.get_missing_unread_ids().unwrap(), vec!["13"]);
+    }
 }
@@ -0,0 +1,69 @@
+use crate::enclosure::Enclosure;
+use crate::utils;
+use cursive::views::Dialog;
+use cursive::CbSink;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread;
+
+/// Downloads enclosures one at a time on a background thread, reporting
+/// each result back to the UI and calling `done` after it.
+#[derive(Clone)]
+pub struct DownloadQueue {
+    sender: Sender<(Enclosure, PathBuf)>,
+    pending: Arc<AtomicUsize>,
+}
+
+impl DownloadQueue {
+    pub fn start(cb_sink: CbSink, done: fn(&mut cursive::Cursive)) -> Self {
+        let (sender, receiver) = mpsc::channel::<(Enclosure, PathBuf)>();
+        let pending = Arc::new(AtomicUsize::new(0));
+        let queued = Arc::clone(&pending);
+        thread::spawn(move || {
+            for (enclosure, dir) in receiver {
+                let message = match download(&enclosure, &dir) {
+                    Ok(path) => format!("Downloaded {}", path.display()),
+                    Err(error) => format!("Couldn't download {}:\n{}", enclosure.url, error),
+                };
+                queued.fetch_sub(1, Ordering::SeqCst);
+                let sent = cb_sink.send(Box::new(move |siv| {
+                    siv.add_layer(Dialog::info(message));
+                    done(siv);
+                }));
+                if sent.is_err() {
+                    break;
+                }
+            }
+        });
+        Self { sender, pending }
+    }
+
+    pub fn push(&self, enclosure: Enclosure, dir: PathBuf) {
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        self.sender.send((enclosure, dir)).unwrap();
+    }
+
+    /// Downloads queued or in progress.
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::SeqCst)
+    }
+}
+
+fn download(enclosure: &Enclosure, dir: &PathBuf) -> Result<PathBuf, String> {
+    std::fs::create_dir_all(dir).map_err(|error| format!("{:#}", error))?;
+    let path = utils::unique_path(dir, &enclosure.file_name());
+    let output = Command::new("curl")
+        .args(["-s", "-S", "-L", "-f", "-o"])
+        .arg(&path)
+        .arg(&enclosure.url)
+        .output()
+        .map_err(|error| format!("{:#}", error))?;
+    if output.status.success() {
+        Ok(path)
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
@@ -0,0 +1,65 @@
+use crate::utils;
+
+#[derive(Debug, Default, Clone)]
+pub struct Enclosure {
+    pub article_id: String,
+    pub url: String,
+    pub mime_type: Option<String>,
+    pub length: Option<i64>,
+}
+
+impl Enclosure {
+    pub fn new(
+        article_id: String,
+        url: String,
+        mime_type: Option<String>,
+        length: Option<i64>,
+    ) -> Self {
+        Self {
+            article_id,
+            url,
+            mime_type,
+            length,
+        }
+    }
+
+    /// File name for downloads: the last path segment of the URL.
+    pub fn file_name(&self) -> String {
+        let path = self.url.split(['?', '#']).next().unwrap_or_default();
+        let name = path
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or_default();
+        let name: String = name
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || "._-".contains(c) {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        if name.trim_matches(['.', '_']).is_empty() {
+            String::from("enclosure")
+        } else {
+            name
+        }
+    }
+
+    pub fn draw(&self) -> String {
+        let mut details = vec![];
+        if let Some(mime_type) = &self.mime_type {
+            details.push(mime_type.clone());
+        }
+        if let Some(length) = self.length {
+            details.push(utils::formatted_size(length));
+        }
+        if details.is_empty() {
+            self.url.clone()
+        } else {
+            format!("{} ({})", self.url, details.join(", "))
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Saving articles to files, e.g. to archive them in a notes repository.
+
+use crate::config::{Export, ExportFormat};
+use crate::db::{ArticleDetails, DB};
+use chrono::{Local, TimeZone};
+use html2text::render::text_renderer::PlainDecorator;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+const WIDTH: usize = 80;
+
+/// Writes the article to the export directory and returns the file path.
+pub fn save_article(
+    db: &DB,
+    settings: &Export,
+    article_id: &str,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let article = db.get_article(article_id.to_string())?;
+    let details = db.get_article_details(article_id)?;
+    let content = details.full_content.as_deref().unwrap_or(&article.content);
+
+    let tags = tags(&details, db.get_article_tags(article_id)?);
+    let contents = match settings.format {
+        ExportFormat::Markdown => to_markdown(&details, &tags, content),
+        ExportFormat::Html => to_html(&details, &tags, content),
+    };
+    let extension = match settings.format {
+        ExportFormat::Markdown => "md",
+        ExportFormat::Html => "html",
+    };
+
+    let dir = settings.dir();
+    fs::create_dir_all(&dir)?;
+    let name = file_name(settings, &details, article_id);
+    let mut path = dir.join(format!("{}.{}", name, extension));
+    // Another article with the same title and date may be saved there
+    let taken = fs::read_to_string(&path).is_ok_and(|existing| existing != contents);
+    if taken && !settings.filename().contains("{id}") {
+        let id = article_id.rsplit('/').next().unwrap_or(article_id);
+        path = dir.join(format!("{}-{}.{}", name, slug(id), extension));
+    }
+    fs::write(&path, contents)?;
+    Ok(path)
+}
+
+/// Saves every starred article, returning the written paths.
+pub fn export_starred(db: &DB, settings: &Export) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    db.get_starred_article_ids()?
+        .iter()
+        .map(|id| save_article(db, settings, id))
+        .collect()
+}
+
+fn tags(details: &ArticleDetails, mut tags: Vec<String>) -> Vec<String> {
+    if details.starred {
+        tags.insert(0, String::from("starred"));
+    }
+    tags
+}
+
+fn iso_date(date: i64) -> String {
+    Local.timestamp_opt(date, 0).unwrap().to_rfc3339()
+}
+
+/// JSON strings are valid YAML scalars, which saves escaping by hand.
+fn yaml_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap()
+}
+
+fn to_markdown(details: &ArticleDetails, tags: &[String], content: &str) -> String {
+    let mut markdown = String::from("---\n");
+    markdown.push_str(&format!("title: {}\n", yaml_string(&details.title)));
+    markdown.push_str(&format!("feed: {}\n", yaml_string(&details.feed_title)));
+    if !details.author.is_empty() {
+        markdown.push_str(&format!("author: {}\n", yaml_string(&details.author)));
+    }
+    markdown.push_str(&format!("date: {}\n", iso_date(details.pub_date)));
+    markdown.push_str(&format!("link: {}\n", yaml_string(&details.link)));
+    markdown.push_str(&format!("tags: {}\n", serde_json::to_string(tags).unwrap()));
+    markdown.push_str("---\n\n");
+    markdown.push_str(&format!("# {}\n\n", details.title));
+    markdown.push_str(&html2text::from_read_with_decorator(
+        content.as_bytes(),
+        WIDTH,
+        PlainDecorator::new(),
+    ));
+    markdown
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn to_html(details: &ArticleDetails, tags: &[String], content: &str) -> String {
+    let meta = |name: &str, value: &str| {
+        format!(
+            "<meta name=\"{}\" content=\"{}\">\n",
+            name,
+            escape_html(value)
+        )
+    };
+    let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n", escape_html(&details.title)));
+    html.push_str(&meta("feed", &details.feed_title));
+    if !details.author.is_empty() {
+        html.push_str(&meta("author", &details.author));
+    }
+    html.push_str(&meta("date", &iso_date(details.pub_date)));
+    html.push_str(&meta("link", &details.link));
+    html.push_str(&meta("keywords", &tags.join(", ")));
+    html.push_str("</head>\n<body>\n");
+    html.push_str(&format!(
+        "<h1><a href=\"{}\">{}</a></h1>\n",
+        escape_html(&details.link),
+        escape_html(&details.title)
+    ));
+    html.push_str(content);
+    html.push_str("\n</body>\n</html>\n");
+    html
+}
+
+/// Fills in the file name template, keeping every part safe to use in a
+/// path on any platform.
+fn file_name(settings: &Export, details: &ArticleDetails, article_id: &str) -> String {
+    let date = Local
+        .timestamp_opt(details.pub_date, 0)
+        .unwrap()
+        .format("%Y-%m-%d")
+        .to_string();
+    settings
+        .filename()
+        .replace("{date}", &date)
+        .replace("{feed}", &slug(&details.feed_title))
+        .replace("{title}", &slug(&details.title))
+        .replace("{id}", &slug(article_id))
+}
+
+fn slug(value: &str) -> String {
+    let slug = value
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    slug.chars().take(80).collect()
+}
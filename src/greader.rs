@@ -1,42 +1,56 @@
-use crate::config::Config;
-use crate::db::{CreateArticleParams, CreateCategoryParams, CreateFeedParams, DB};
-use crate::utils;
+use crate::config::Profile;
+use crate::db::{CreateArticleParams, CreateCategoryParams, CreateFeedParams, SyncState, DB};
+use crate::enclosure::Enclosure;
+use crate::rules::{RuleMatch, Rules};
 use chrono::Local;
 use serde::{Deserialize, Serialize};
-use std::fs::{self, File};
-use std::io::Result;
-use std::io::Write;
+use std::io::{Error, ErrorKind, Result};
+use std::path::PathBuf;
 use std::process::Command;
 
 const TOKEN_PREFIX: &str = "Auth";
+const READING_LIST: &str = "user/-/state/com.google/reading-list";
+const STARRED: &str = "user/-/state/com.google/starred";
+const READ: &str = "user/-/state/com.google/read";
+const ITEMS_PER_REQUEST: usize = 250;
+const LABEL: &str = "user/-/label/";
 
 #[derive(Clone, Debug, Default)]
 pub struct Greader {
     cltoken: String,
     api_url: String,
+    db_path: PathBuf,
+    rules: Rules,
 }
 
 impl Greader {
-    pub fn login(config: Config) -> Result<Greader> {
-        if config.fresh_rss_api_password.is_none()
-            || config.fresh_rss_api_user.is_none()
-            || config.fresh_rss_api_password.is_none()
-        {
-            panic!("Some of the FreshRss credentials are missing");
-        }
+    pub fn login(profile: &Profile) -> Result<Greader> {
+        let (api_url, user, password) = match (
+            profile.fresh_rss_api_url.as_ref(),
+            profile.fresh_rss_api_user.as_ref(),
+            profile.fresh_rss_api_password.as_ref(),
+        ) {
+            (Some(api_url), Some(user), Some(password)) => (api_url, user, password),
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "Some of the FreshRss credentials are missing for profile `{}`",
+                        profile.name
+                    ),
+                ))
+            }
+        };
 
         let output = Command::new("curl")
             .args([
                 "-X",
                 "POST",
-                &format!(
-                    "{}/accounts/ClientLogin",
-                    config.fresh_rss_api_url.as_ref().unwrap()
-                ),
+                &format!("{}/accounts/ClientLogin", api_url),
                 "-d",
-                &format!("Email={}", config.fresh_rss_api_user.unwrap()),
+                &format!("Email={}", user),
                 "-d",
-                &format!("Passwd={}", config.fresh_rss_api_password.unwrap()),
+                &format!("Passwd={}", password),
             ])
             .output()?;
         let out = String::from_utf8(output.stdout).unwrap();
@@ -51,52 +65,78 @@ impl Greader {
         }
         Ok(Greader {
             cltoken: token,
-            api_url: config.fresh_rss_api_url.unwrap(),
+            api_url: api_url.clone(),
+            db_path: profile.db_path.clone(),
+            rules: Rules::default(),
         })
     }
 
-    pub fn get_unred_articles_content(&self, continuation: Option<String>) -> Result<()> {
-        let last_synced = get_last_sync_time();
-        let cont = continuation.unwrap_or("".to_string());
-        let output = Command::new("curl")
-            .args([
-                "-s",
-                "-H",
-                &format!("Authorization:GoogleLogin auth={}", self.cltoken),
-                &format!(
-                    "{}/reader/api/0/stream/contents?s=user/-/state/com.google/reading-list&xt=user/-/state/com.google/read&n=1000&r=n&c={}&ot={}",
-                    self.api_url,
-                    cont,
-                    last_synced.trim(),
-                ),
-            ])
-            .output()?;
-        let out = String::from_utf8(output.stdout).unwrap();
-        let reading_list: ReadingList = serde_json::from_str(&out).unwrap();
-        let db = DB::new();
-        for item in reading_list.items {
-            db.create_article(CreateArticleParams {
-                id: item.id,
-                link: item.canonical[0].href.clone(),
-                title: item.title,
-                description: String::from(""),
-                content: item.summary.content,
-                unread: 1,
-                feed_id: item.origin.stream_id,
-                pub_date: item.published,
-                author: item.author,
+    /// Rules applied to articles stored by the sync.
+    pub fn with_rules(mut self, rules: Rules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    fn db(&self) -> DB {
+        DB::new(&self.db_path).with_rules(self.rules.clone())
+    }
+
+    pub fn get_unred_articles_content(&self) -> Result<()> {
+        let db = self.db();
+        let state = db.get_sync_state(READING_LIST).unwrap();
+        let started = Local::now().timestamp();
+        let last_synced = state.last_synced.map(|t| t.to_string()).unwrap_or_default();
+        // Resume an interrupted sync from the last page that was stored
+        let mut continuation = state.continuation;
+        loop {
+            let output = Command::new("curl")
+                .args([
+                    "-s",
+                    "-H",
+                    &format!("Authorization:GoogleLogin auth={}", self.cltoken),
+                    &format!(
+                        "{}/reader/api/0/stream/contents?s={}&xt=user/-/state/com.google/read&n=1000&r=n&c={}&ot={}",
+                        self.api_url,
+                        READING_LIST,
+                        continuation.as_deref().unwrap_or(""),
+                        last_synced,
+                    ),
+                ])
+                .output()?;
+            let out = String::from_utf8(output.stdout).unwrap();
+            let reading_list: ReadingList = serde_json::from_str(&out).unwrap();
+            let matches = store_items(&db, reading_list.items);
+            self.push_rule_actions(&matches)?;
+
+            continuation = reading_list.continuation;
+            if continuation.is_none() {
+                db.save_sync_state(&SyncState {
+                    stream_id: READING_LIST.to_string(),
+                    last_synced: Some(started),
+                    continuation: None,
+                    server_updated: Some(reading_list.updated),
+                })
+                .unwrap();
+                break;
+            }
+            db.save_sync_state(&SyncState {
+                stream_id: READING_LIST.to_string(),
+                last_synced: state.last_synced,
+                continuation: continuation.clone(),
+                server_updated: state.server_updated,
             })
             .unwrap();
         }
-
-        if let Some(con) = reading_list.continuation {
-            self.get_unred_articles_content(Some(con)).unwrap();
-        } else {
-            write_last_sync_time()?;
-        }
         Ok(())
     }
 
+    /// When the reading list stored in `db` was last fully synced.
+    pub fn last_synced(db: &DB) -> Option<i64> {
+        db.get_sync_state(READING_LIST)
+            .ok()
+            .and_then(|state| state.last_synced)
+    }
+
     pub fn get_subscription_list(&self) -> Result<()> {
         let output = Command::new("curl")
             .args([
@@ -111,7 +151,7 @@ impl Greader {
             .output()?;
         let out = String::from_utf8(output.stdout).unwrap();
         let subs: Subscriptions = serde_json::from_str(&out).unwrap();
-        let db = DB::new();
+        let db = self.db();
         for sub in subs.subscriptions {
             let categories = sub.categories;
             for category in &categories {
@@ -132,13 +172,20 @@ impl Greader {
                 // FIXME: store it as cat_id1,cat_id2,cat_id3?
                 category_id: categories[0].id.clone(),
                 id: sub.id,
+                icon_url: Some(sub.icon_url).filter(|url| !url.is_empty()),
+                categories: categories
+                    .iter()
+                    .map(|category| category.label.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                last_error: sub.error.filter(|error| !error.is_empty()),
             })
             .unwrap();
         }
         Ok(())
     }
 
-    pub fn get_tag_list(&self) -> Result<()> {
+    pub fn get_tag_list(&self) -> Result<Vec<Tag>> {
         let output = Command::new("curl")
             .args([
                 "-s",
@@ -148,10 +195,47 @@ impl Greader {
             ])
             .output()?;
         let out = String::from_utf8(output.stdout).unwrap();
-        let subs: Tags = serde_json::from_str(&out).unwrap();
-        for sub in subs.tags {
-            println!("{:#?}", sub);
+        let tags: Tags = serde_json::from_str(&out).unwrap();
+        Ok(tags.tags)
+    }
+
+    /// Replaces the local tags with the labels on the server. Labels used as
+    /// folders are categories, not tags.
+    pub fn sync_tags(&self) -> Result<()> {
+        let db = self.db();
+        let folders = folder_names(&db);
+        let mut tags = Vec::new();
+        for tag in self.get_tag_list()? {
+            let name = match label_name(&tag.id) {
+                Some(name) if tag.r#type.as_deref() != Some("folder") => name,
+                _ => continue,
+            };
+            if !folders.iter().any(|folder| folder == name) {
+                let ids = self.get_item_ids(&tag.id, "")?;
+                tags.push((name.to_string(), ids));
+            }
         }
+        db.replace_tags(&tags).unwrap();
+        Ok(())
+    }
+
+    /// Takes the starred state from the server, so articles starred or
+    /// unstarred elsewhere are kept or pruned accordingly.
+    pub fn sync_starred(&self) -> Result<()> {
+        let ids = self.get_item_ids(STARRED, "")?;
+        self.db().replace_starred(&ids).unwrap();
+        Ok(())
+    }
+
+    pub fn add_article_tag(&self, article_id: &str, tag: &str) -> Result<()> {
+        self.add_tag(&[article_id.to_string()], &format!("{}{}", LABEL, tag))?;
+        self.db().add_article_tag(article_id, tag).unwrap();
+        Ok(())
+    }
+
+    pub fn remove_article_tag(&self, article_id: &str, tag: &str) -> Result<()> {
+        self.edit_tag(&[article_id.to_string()], &format!("r={}{}", LABEL, tag))?;
+        self.db().remove_article_tag(article_id, tag).unwrap();
         Ok(())
     }
 
@@ -170,11 +254,87 @@ impl Greader {
                 "a=user/-/state/com.google/read",
             ])
             .output()?;
-        let db = DB::new();
+        let db = self.db();
         db.mark_article_as_read(article_id).unwrap();
         Ok(())
     }
 
+    pub fn mark_articles_as_read(&self, article_ids: &[String]) -> Result<()> {
+        self.add_tag(article_ids, READ)?;
+        let db = self.db();
+        for article_id in article_ids {
+            db.mark_article_as_read(article_id).unwrap();
+        }
+        Ok(())
+    }
+
+    /// Adds `tag` to many items at once.
+    fn add_tag(&self, ids: &[String], tag: &str) -> Result<()> {
+        self.edit_tag(ids, &format!("a={}", tag))
+    }
+
+    /// Sends `change`, `a=<tag>` or `r=<tag>`, for the items, failing when
+    /// the request doesn't go through so the caller can keep the local
+    /// state as it was.
+    fn edit_tag(&self, ids: &[String], change: &str) -> Result<()> {
+        for chunk in ids.chunks(ITEMS_PER_REQUEST) {
+            let mut args = vec![
+                String::from("-s"),
+                String::from("-S"),
+                String::from("-f"),
+                String::from("-H"),
+                format!("Authorization:GoogleLogin auth={}", self.cltoken),
+                String::from("-X"),
+                String::from("POST"),
+                format!("{}/reader/api/0/edit-tag", self.api_url),
+                String::from("--data-urlencode"),
+                change.to_string(),
+            ];
+            for id in chunk {
+                args.push(String::from("--data-urlencode"));
+                args.push(format!("i={}", id));
+            }
+            let output = Command::new("curl").args(args).output()?;
+            if !output.status.success() {
+                return Err(Error::other(format!(
+                    "Couldn't update the server: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the read, starred and tag state set by rules to the server,
+    /// which would otherwise revert it on the next sync.
+    fn push_rule_actions(&self, matches: &[RuleMatch]) -> Result<()> {
+        let ids = |changed: fn(&RuleMatch) -> bool| -> Vec<String> {
+            matches
+                .iter()
+                .filter(|m| changed(m))
+                .map(|m| m.target.article_id.clone())
+                .collect()
+        };
+        self.add_tag(&ids(|m| m.actions.mark_read && m.target.unread), READ)?;
+        self.add_tag(&ids(|m| m.actions.star && !m.target.starred), STARRED)?;
+        for rule_match in matches {
+            for tag in &rule_match.actions.tags {
+                let label = format!("{}{}", LABEL, tag);
+                self.add_tag(std::slice::from_ref(&rule_match.target.article_id), &label)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Re-applies the rules to all stored articles, see `DB::apply_rules`.
+    pub fn apply_rules(&self, dry_run: bool) -> Result<Vec<RuleMatch>> {
+        let matches = self.db().apply_rules(dry_run).unwrap();
+        if !dry_run {
+            self.push_rule_actions(&matches)?;
+        }
+        Ok(matches)
+    }
+
     pub fn mark_article_as_unread(&self, article_id: &str) -> Result<()> {
         Command::new("curl")
             .args([
@@ -190,52 +350,176 @@ impl Greader {
                 "r=user/-/state/com.google/read",
             ])
             .output()?;
-        let db = DB::new();
+        let db = self.db();
         db.mark_article_as_unread(article_id).unwrap();
         Ok(())
     }
 
-    pub fn mark_articles_as_read_except(&self) -> Result<()> {
-        let output = Command::new("curl")
-            .args([
-                "-s",
-                "-H",
-                &format!("Authorization:GoogleLogin auth={}", self.cltoken),
-                &format!("{}/reader/api/0/stream/items/ids?output=json&s=user/-/state/com.google/reading-list&xt=user/-/state/com.google/read&n=10000&r=n", self.api_url),
-            ])
-            .output()?;
-        let out = String::from_utf8(output.stdout).unwrap();
-        let unread_items: UnreadItemIds = serde_json::from_str(&out).unwrap();
+    /// Fetches every id of the given stream, following continuations.
+    fn get_item_ids(&self, stream_id: &str, exclude: &str) -> Result<Vec<String>> {
         let mut ids = vec![];
-        for item in unread_items.item_refs {
-            ids.push(item.id);
+        let mut continuation: Option<String> = None;
+        loop {
+            // Label ids may contain spaces, `&` or `+`
+            let query = url::form_urlencoded::Serializer::new(String::new())
+                .append_pair("output", "json")
+                .append_pair("s", stream_id)
+                .append_pair("xt", exclude)
+                .append_pair("n", "10000")
+                .append_pair("r", "n")
+                .append_pair("c", continuation.as_deref().unwrap_or(""))
+                .finish();
+            let output = Command::new("curl")
+                .args([
+                    "-s",
+                    "-S",
+                    "-f",
+                    "-H",
+                    &format!("Authorization:GoogleLogin auth={}", self.cltoken),
+                    &format!("{}/reader/api/0/stream/items/ids?{}", self.api_url, query),
+                ])
+                .output()?;
+            if !output.status.success() {
+                return Err(Error::other(format!(
+                    "Couldn't fetch the items of `{}`: {}",
+                    stream_id,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+            let item_ids: UnreadItemIds = serde_json::from_slice(&output.stdout)?;
+            ids.extend(item_ids.item_refs.into_iter().map(|item| item.id));
+
+            continuation = item_ids.continuation;
+            if continuation.is_none() {
+                break;
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Fetches the content of the given items, `ids` may be short ids.
+    fn get_articles_content(&self, ids: &[String]) -> Result<()> {
+        let db = self.db();
+        for chunk in ids.chunks(ITEMS_PER_REQUEST) {
+            let mut args = vec![
+                String::from("-s"),
+                String::from("-H"),
+                format!("Authorization:GoogleLogin auth={}", self.cltoken),
+                String::from("-X"),
+                String::from("POST"),
+                format!(
+                    "{}/reader/api/0/stream/items/contents?output=json",
+                    self.api_url
+                ),
+            ];
+            for id in chunk {
+                args.push(String::from("-d"));
+                args.push(format!("i={}", id));
+            }
+            let output = Command::new("curl").args(args).output()?;
+            let out = String::from_utf8(output.stdout).unwrap();
+            let reading_list: ReadingList = serde_json::from_str(&out).unwrap();
+            let matches = store_items(&db, reading_list.items);
+            self.push_rule_actions(&matches)?;
         }
-        let db = DB::new();
-        db.mark_articles_as_read_except(ids).unwrap();
         Ok(())
     }
 
+    /// Makes the local read state match the server: articles missing from the
+    /// server's unread list become read, the ones marked unread on the server
+    /// become unread again and the ones we don't have yet are fetched.
+    pub fn mark_articles_as_read_except(&self) -> Result<()> {
+        let ids = self.get_item_ids(READING_LIST, READ)?;
+        let db = self.db();
+        db.mark_articles_as_read_except(ids).unwrap();
+        let missing = db.get_missing_unread_ids().unwrap();
+        self.get_articles_content(&missing)
+    }
+
     pub fn sync(&self) -> Result<()> {
         self.get_subscription_list()?;
-        self.get_unred_articles_content(None)?;
+        self.get_unred_articles_content()?;
         self.mark_articles_as_read_except()?;
+        self.sync_starred()?;
+        self.sync_tags()?;
         Ok(())
     }
+
+    /// Drops the local cache together with the sync cursors and fetches
+    /// everything from scratch.
+    pub fn full_resync(&self) -> Result<()> {
+        self.db().reset_local_cache().unwrap();
+        self.sync()
+    }
 }
 
-fn get_last_sync_time() -> String {
-    let contents = fs::read_to_string(format!("{}/last_synced", utils::get_config_dir()));
-    match contents {
-        Ok(t) => t,
-        Err(_) => "".to_string(),
+/// Name of the tag from a label stream id like `user/-/label/<name>`, the
+/// user part may also be the numeric user id.
+fn label_name(stream_id: &str) -> Option<&str> {
+    let (user, name) = stream_id.split_once("/label/")?;
+    if user.starts_with("user/") && !name.is_empty() {
+        Some(name)
+    } else {
+        None
     }
 }
 
-fn write_last_sync_time() -> Result<()> {
-    let mut output = File::create(format!("{}/last_synced", utils::get_config_dir()))?;
-    let now = Local::now();
-    write!(output, "{}", now.timestamp())?;
-    Ok(())
+/// Names of the labels used as folders, compared by name since the user
+/// part of label ids isn't always `-`.
+fn folder_names(db: &DB) -> Vec<String> {
+    db.get_categories()
+        .unwrap()
+        .into_iter()
+        .map(|category| {
+            label_name(&category.id)
+                .map(String::from)
+                .unwrap_or(category.label)
+        })
+        .collect()
+}
+
+/// Stores the items, returning the ones matched by rules.
+fn store_items(db: &DB, items: Vec<Item>) -> Vec<RuleMatch> {
+    let folders = folder_names(db);
+    let mut matches = Vec::new();
+    for item in items {
+        let tags: Vec<String> = item
+            .categories
+            .iter()
+            .filter_map(|category| label_name(category))
+            .filter(|name| !folders.iter().any(|folder| folder == name))
+            .map(String::from)
+            .collect();
+        let article_id = item.id.clone();
+        for enclosure in &item.enclosure {
+            db.create_enclosure(&Enclosure::new(
+                item.id.clone(),
+                enclosure.href.clone(),
+                enclosure.r#type.clone(),
+                enclosure.length(),
+            ))
+            .unwrap();
+        }
+        let rule_match = db
+            .create_article(CreateArticleParams {
+                id: item.id,
+                link: item.canonical[0].href.clone(),
+                title: item.title,
+                description: String::from(""),
+                content: item.summary.content,
+                unread: 1,
+                feed_id: item.origin.stream_id,
+                pub_date: item.published,
+                author: item.author,
+                starred: item.categories.iter().any(|c| c == STARRED),
+            })
+            .unwrap();
+        for tag in &tags {
+            db.add_article_tag(&article_id, tag).unwrap();
+        }
+        matches.extend(rule_match);
+    }
+    matches
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -252,7 +536,11 @@ pub struct Subscription {
     pub categories: Vec<Category>,
     pub url: String,
     pub html_url: String,
+    #[serde(default)]
     pub icon_url: String,
+    /// Fetch error reported by servers that expose one
+    #[serde(default)]
+    pub error: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -274,6 +562,7 @@ pub struct Tags {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ReadingList {
+    #[serde(default)]
     id: String,
     updated: i64,
     items: Vec<Item>,
@@ -290,6 +579,27 @@ pub struct Item {
     categories: Vec<String>,
     origin: ItemOrigin,
     author: Option<String>,
+    #[serde(default, alias = "enclosures")]
+    enclosure: Vec<ItemEnclosure>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ItemEnclosure {
+    href: String,
+    r#type: Option<String>,
+    /// Some servers send the length as a string
+    length: Option<serde_json::Value>,
+}
+
+impl ItemEnclosure {
+    fn length(&self) -> Option<i64> {
+        match self.length.as_ref()? {
+            serde_json::Value::Number(n) => n.as_i64(),
+            serde_json::Value::String(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+        .filter(|l| *l > 0)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -0,0 +1,171 @@
+//! Keyword highlighting from `[[highlights]]`.
+
+use crate::config::Highlight;
+use cursive::theme::{Color, ColorStyle, ColorType, Effect, Style};
+use cursive::utils::markup::StyledString;
+use html5ever::parse_document;
+use html5ever::tendril::TendrilSink;
+use markup5ever_rcdom::{Handle, NodeData, RcDom};
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+
+#[derive(Debug, Clone, Default)]
+pub struct Highlighter {
+    patterns: Vec<(Regex, Style)>,
+}
+
+impl Highlighter {
+    pub fn new(highlights: &[Highlight]) -> Result<Highlighter, String> {
+        let mut patterns = Vec::new();
+        for highlight in highlights {
+            let pattern = if highlight.regex {
+                highlight.pattern.clone()
+            } else {
+                regex::escape(&highlight.pattern)
+            };
+            let regex = RegexBuilder::new(&pattern)
+                .case_insensitive(!highlight.case_sensitive)
+                .build()
+                .map_err(|error| {
+                    format!("Invalid highlight `{}`:\n{}", highlight.pattern, error)
+                })?;
+            patterns.push((regex, style(highlight)?));
+        }
+        Ok(Highlighter { patterns })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.patterns.iter().any(|(regex, _)| regex.is_match(text))
+    }
+
+    /// Like `is_match` for article HTML, looking only at the text shown in
+    /// the reader, not at tags, attributes or URLs.
+    pub fn is_match_html(&self, html: &str) -> bool {
+        !self.is_empty() && self.is_match(&text_content(html))
+    }
+
+    /// Byte ranges of the matches in `text` with their styles, sorted and
+    /// without overlaps, earlier patterns win.
+    pub fn ranges(&self, text: &str) -> Vec<(Range<usize>, Style)> {
+        let mut ranges: Vec<(Range<usize>, Style)> = Vec::new();
+        for (regex, style) in &self.patterns {
+            for found in regex.find_iter(text).filter(|found| !found.is_empty()) {
+                let range = found.range();
+                if !ranges
+                    .iter()
+                    .any(|(r, _)| r.start < range.end && range.start < r.end)
+                {
+                    ranges.push((range, *style));
+                }
+            }
+        }
+        ranges.sort_by_key(|(range, _)| range.start);
+        ranges
+    }
+
+    /// Appends `text` in `style`, with the matches highlighted on top.
+    pub fn append(&self, target: &mut StyledString, text: &str, style: Style) {
+        append_ranges(target, text, 0, style, &self.ranges(text));
+    }
+}
+
+/// Appends `span`, which starts at byte `offset` of the text `ranges` were
+/// found in, splitting it where highlighted ranges begin and end.
+pub fn append_ranges(
+    target: &mut StyledString,
+    span: &str,
+    offset: usize,
+    style: Style,
+    ranges: &[(Range<usize>, Style)],
+) {
+    let end = offset + span.len();
+    let mut position = offset;
+    for (range, highlight) in ranges {
+        if range.end <= position || range.start >= end {
+            continue;
+        }
+        let start = range.start.max(position);
+        if start > position {
+            target.append_styled(&span[position - offset..start - offset], style);
+        }
+        let stop = range.end.min(end);
+        target.append_styled(
+            &span[start - offset..stop - offset],
+            style.combine(*highlight),
+        );
+        position = stop;
+    }
+    if position < end {
+        target.append_styled(&span[position - offset..], style);
+    }
+}
+
+/// The text of `html` without markup, scripts and styles, with each block on
+/// a line of its own.
+fn text_content(html: &str) -> String {
+    let dom = match parse_document(RcDom::default(), Default::default())
+        .from_utf8()
+        .read_from(&mut html.as_bytes())
+    {
+        Ok(dom) => dom,
+        Err(_) => return String::new(),
+    };
+    let mut text = String::new();
+    append_text(&dom.document, &mut text);
+    text
+}
+
+fn append_text(node: &Handle, text: &mut String) {
+    let tag = match node.data {
+        NodeData::Text { ref contents } => {
+            text.push_str(&contents.borrow());
+            return;
+        }
+        NodeData::Element { ref name, .. } => Some(name.local.to_string()),
+        _ => None,
+    };
+    if let Some("script" | "style" | "template") = tag.as_deref() {
+        return;
+    }
+    for child in node.children.borrow().iter() {
+        append_text(child, text);
+    }
+    if let Some(
+        "address" | "article" | "blockquote" | "br" | "dd" | "div" | "dt" | "figcaption" | "h1"
+        | "h2" | "h3" | "h4" | "h5" | "h6" | "hr" | "li" | "p" | "pre" | "section" | "td" | "th"
+        | "tr",
+    ) = tag.as_deref()
+    {
+        text.push('\n');
+    }
+}
+
+fn style(highlight: &Highlight) -> Result<Style, String> {
+    let parse = |color: &Option<String>| match color {
+        Some(name) => Color::parse(name).map(ColorType::Color).ok_or(format!(
+            "Unknown colour `{}` in highlight `{}`",
+            name, highlight.pattern
+        )),
+        None => Ok(ColorType::InheritParent),
+    };
+    let mut style = Style::from(ColorStyle::new(
+        match highlight.color {
+            None if highlight.background.is_none() => {
+                ColorType::Color(Color::Light(cursive::theme::BaseColor::Yellow))
+            }
+            _ => parse(&highlight.color)?,
+        },
+        parse(&highlight.background)?,
+    ));
+    if highlight.bold {
+        style = style.combine(Effect::Bold);
+    }
+    if highlight.underline {
+        style = style.combine(Effect::Underline);
+    }
+    Ok(style)
+}
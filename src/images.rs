@@ -0,0 +1,229 @@
+use crate::utils;
+use cursive::theme::{Color, ColorStyle, Style};
+use cursive::utils::markup::StyledString;
+use cursive::CbSink;
+use html5ever::serialize::{serialize, SerializeOpts};
+use html5ever::tendril::{StrTendril, TendrilSink};
+use html5ever::{local_name, namespace_url, ns, parse_document, QualName};
+use image::imageops::FilterType;
+use image::DynamicImage;
+use markup5ever_rcdom::{Handle, Node, NodeData, RcDom, SerializableHandle};
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::rc::Rc;
+use std::thread;
+use url::Url;
+
+/// Tallest preview in terminal rows, so a poster doesn't take whole screens.
+const MAX_ROWS: u32 = 40;
+const MARKER_START: char = '\u{E000}';
+const MARKER_END: char = '\u{E001}';
+
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub url: String,
+    pub alt: String,
+}
+
+impl Image {
+    pub fn cache_path(&self) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        self.url.hash(&mut hasher);
+        utils::get_cache_dir()
+            .join("images")
+            .join(format!("{:016x}", hasher.finish()))
+    }
+
+    pub fn placeholder(&self) -> String {
+        if self.alt.is_empty() {
+            String::from("[image]")
+        } else {
+            format!("[image: {}]", self.alt)
+        }
+    }
+}
+
+/// Replaces every `<img>` with a block holding a marker, so html2text puts
+/// each image on a line of its own which `image_at` can then recognise.
+pub fn mark_images(html: &str, base: Option<&Url>) -> (String, Vec<Image>) {
+    let dom = match parse_document(RcDom::default(), Default::default())
+        .from_utf8()
+        .read_from(&mut html.as_bytes())
+    {
+        Ok(dom) => dom,
+        Err(_) => return (html.to_string(), Vec::new()),
+    };
+    let mut images = Vec::new();
+    replace_images(&dom.document, base, &mut images);
+
+    let mut marked = Vec::new();
+    let document = SerializableHandle::from(dom.document.clone());
+    if serialize(&mut marked, &document, SerializeOpts::default()).is_err() {
+        return (html.to_string(), Vec::new());
+    }
+    (String::from_utf8_lossy(&marked).to_string(), images)
+}
+
+fn replace_images(node: &Handle, base: Option<&Url>, images: &mut Vec<Image>) {
+    let mut children = node.children.borrow_mut();
+    for child in children.iter_mut() {
+        let image = match child.data {
+            NodeData::Element {
+                ref name,
+                ref attrs,
+                ..
+            } if name.local == local_name!("img") => {
+                let attrs = attrs.borrow();
+                let attr = |key: &str| {
+                    attrs
+                        .iter()
+                        .find(|a| &*a.name.local == key)
+                        .map(|a| a.value.to_string())
+                };
+                attr("src")
+                    .and_then(|src| utils::resolve_link(base, &src))
+                    .map(|url| Image {
+                        url,
+                        alt: attr("alt").unwrap_or_default(),
+                    })
+            }
+            _ => {
+                replace_images(child, base, images);
+                continue;
+            }
+        };
+        if let Some(image) = image {
+            let text = format!("{}{}{}", MARKER_START, images.len(), MARKER_END);
+            images.push(image);
+            *child = marker_block(&text);
+        }
+    }
+}
+
+fn marker_block(text: &str) -> Handle {
+    let block = Node::new(NodeData::Element {
+        name: QualName::new(None, ns!(html), local_name!("div")),
+        attrs: RefCell::new(Vec::new()),
+        template_contents: RefCell::new(None),
+        mathml_annotation_xml_integration_point: false,
+    });
+    let text = Node::new(NodeData::Text {
+        contents: RefCell::new(StrTendril::from(text)),
+    });
+    text.parent.set(Some(Rc::downgrade(&block)));
+    block.children.borrow_mut().push(text);
+    block
+}
+
+/// Index of the image whose marker makes up the rendered `line`.
+pub fn image_at(line: &str) -> Option<usize> {
+    let line = line.trim_matches(|c: char| c.is_whitespace() || c == '>' || c == '*');
+    line.strip_prefix(MARKER_START)?
+        .strip_suffix(MARKER_END)?
+        .parse()
+        .ok()
+}
+
+/// Downloads the images missing from the cache, then runs `done` on the UI
+/// thread if anything new arrived.
+pub fn prefetch<F>(images: Vec<Image>, cb_sink: CbSink, done: F)
+where
+    F: FnOnce(&mut cursive::Cursive) + Send + 'static,
+{
+    let missing: Vec<Image> = images
+        .into_iter()
+        .filter(|image| !image.cache_path().exists())
+        .collect();
+    if missing.is_empty() {
+        return;
+    }
+    thread::spawn(move || {
+        let mut fetched = false;
+        for image in missing {
+            fetched |= download(&image).is_some();
+        }
+        if fetched {
+            cb_sink.send(Box::new(done)).ok();
+        }
+    });
+}
+
+fn download(image: &Image) -> Option<()> {
+    let path = image.cache_path();
+    fs::create_dir_all(path.parent()?).ok()?;
+    let partial = path.with_extension("part");
+    let status = Command::new("curl")
+        .args(["-s", "-L", "-f", "-o"])
+        .arg(&partial)
+        .arg(&image.url)
+        .status()
+        .ok()?;
+    if !status.success() {
+        fs::remove_file(&partial).ok();
+        return None;
+    }
+    fs::rename(&partial, &path).ok()
+}
+
+/// Images decoded from the cache, kept so rewrapping an article doesn't
+/// decode them again.
+#[derive(Default)]
+pub struct Pictures {
+    /// `None` for files that couldn't be decoded
+    decoded: HashMap<String, Option<DynamicImage>>,
+}
+
+impl Pictures {
+    /// The decoded image, once it's in the cache.
+    pub fn get(&mut self, image: &Image) -> Option<&DynamicImage> {
+        if !self.decoded.contains_key(&image.url) {
+            let path = image.cache_path();
+            if !path.exists() {
+                return None;
+            }
+            self.decoded.insert(image.url.clone(), decode(&path));
+        }
+        self.decoded.get(&image.url)?.as_ref()
+    }
+}
+
+/// Cached files have no extension, so the format is guessed from the
+/// contents.
+fn decode(path: &Path) -> Option<DynamicImage> {
+    image::io::Reader::open(path)
+        .ok()?
+        .with_guessed_format()
+        .ok()?
+        .decode()
+        .ok()
+}
+
+/// Draws an image `width` cells wide using upper half blocks, each cell
+/// showing two pixels through its foreground and background colours.
+pub fn render_half_blocks(picture: &DynamicImage, width: usize) -> StyledString {
+    let columns = (width as u32).min(picture.width()).max(1);
+    let rows = (picture.height() * columns / picture.width().max(1) / 2).clamp(1, MAX_ROWS);
+    let picture = picture
+        .resize_exact(columns, rows * 2, FilterType::Triangle)
+        .to_rgb8();
+
+    let mut text = StyledString::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let top = picture.get_pixel(column, row * 2);
+            let bottom = picture.get_pixel(column, row * 2 + 1);
+            let style = Style::from(ColorStyle::new(
+                Color::Rgb(top[0], top[1], top[2]),
+                Color::Rgb(bottom[0], bottom[1], bottom[2]),
+            ));
+            text.append_styled("▀", style);
+        }
+        text.append_plain("\n");
+    }
+    text
+}
@@ -1,9 +1,20 @@
 pub mod article;
+pub mod article_body;
 pub mod category;
+pub mod clipboard;
 pub mod config;
 pub mod db;
+pub mod downloads;
+pub mod enclosure;
+pub mod export;
 pub mod feed;
 pub mod greader;
+pub mod highlight;
+pub mod images;
+pub mod readability;
+pub mod rules;
+pub mod share;
+pub mod stats;
 pub mod tree_entry;
 pub mod ui;
 pub mod utils;
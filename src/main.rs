@@ -1,25 +1,173 @@
+use clap::{Parser, Subcommand};
 use news_rss::config::Config;
 use news_rss::db::DB;
+use news_rss::export;
 use news_rss::greader::Greader;
+use news_rss::highlight::Highlighter;
+use news_rss::rules::{RuleMatch, Rules};
+use news_rss::stats::{self, Stats};
 use news_rss::ui::UI;
 use news_rss::utils;
+use std::error::Error;
+use std::fs;
 use std::path::PathBuf;
 
+#[derive(Parser, Debug)]
+#[command(about = "Terminal client for FreshRSS")]
+struct Cli {
+    /// Name of the `[profiles.<name>]` account from config.toml to open
+    #[arg(long, short, env = "NEWS_RSS_PROFILE")]
+    profile: Option<String>,
+
+    /// Path to config.toml, defaults to `$XDG_CONFIG_HOME/news-rss/config.toml`
+    #[arg(long, short, env = "NEWS_RSS_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Path to the database, defaults to `$XDG_DATA_HOME/news-rss/news.db`
+    #[arg(long, env = "NEWS_RSS_DB")]
+    db: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Save all starred articles as configured in `[export]` and exit
+    ExportStarred {
+        /// Directory to write to instead of `export.dir`
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
+    /// Apply `[[rules]]` to all stored articles and list the matches
+    ApplyRules {
+        /// Only list the articles that would match, changing nothing
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Print reading stats: articles read, read ratios and dead feeds
+    Stats {
+        /// Print the stats as JSON
+        #[arg(long)]
+        json: bool,
+        /// List feeds without posts in this many months as dead
+        #[arg(long, default_value_t = stats::DEAD_AFTER_MONTHS)]
+        dead_after: i64,
+    },
+}
+
 fn main() {
-    let path = PathBuf::from(format!("{}/config.toml", utils::get_config_dir()));
-    let config = Config::from(&path.display().to_string());
+    let cli = Cli::parse();
+    let path = match cli.config {
+        Some(path) => path,
+        None => {
+            let config_dir = utils::get_config_dir();
+            if let Err(error) = fs::create_dir_all(&config_dir) {
+                println!(
+                    "Something went wrong while creating {}:\n{:#}",
+                    config_dir.display(),
+                    error
+                );
+                return;
+            }
+            config_dir.join("config.toml")
+        }
+    };
+    let config = Config::from(&path);
     match config {
-        Ok(config) => {
-            DB::new()
+        Ok(mut config) => {
+            let mut profile = match config.profile(cli.profile.as_deref()) {
+                Ok(profile) => profile,
+                Err(error) => {
+                    println!("{:#}", error);
+                    return;
+                }
+            };
+            if let Some(db) = cli.db {
+                profile.db_path = db;
+            }
+            DB::new(&profile.db_path)
                 .create_db()
                 .expect("Something went wrong while creating DB");
-            let greader = Greader::login(config.clone()).unwrap();
+            if let Some(Command::ExportStarred { dir }) = cli.command {
+                if dir.is_some() {
+                    config.export.dir = dir;
+                }
+                let db = DB::new(&profile.db_path);
+                match export::export_starred(&db, &config.export) {
+                    Ok(paths) => println!(
+                        "Saved {} articles to {}",
+                        paths.len(),
+                        config.export.dir().display()
+                    ),
+                    Err(error) => println!("Something went wrong while exporting:\n{:#}", error),
+                }
+                return;
+            }
+            if let Some(Command::Stats { json, dead_after }) = cli.command {
+                match Stats::collect(&DB::new(&profile.db_path), dead_after) {
+                    Ok(stats) if json => {
+                        println!("{}", serde_json::to_string_pretty(&stats).unwrap())
+                    }
+                    Ok(stats) => print!("{}", stats.report()),
+                    Err(error) => {
+                        println!("Something went wrong while reading stats:\n{:#}", error)
+                    }
+                }
+                return;
+            }
+            if let Err(error) = Highlighter::new(&config.highlights) {
+                println!("{}", error);
+                return;
+            }
+            let rules = match Rules::new(&config.rules) {
+                Ok(rules) => rules,
+                Err(error) => {
+                    println!("{}", error);
+                    return;
+                }
+            };
+            if let Some(Command::ApplyRules { dry_run: true }) = cli.command {
+                let db = DB::new(&profile.db_path).with_rules(rules);
+                print_rule_matches(db.apply_rules(true).map_err(|error| error.into()));
+                return;
+            }
+            let greader = match Greader::login(&profile) {
+                Ok(greader) => greader.with_rules(rules),
+                Err(error) => {
+                    println!("Something went wrong while logging in:\n{:#}", error);
+                    return;
+                }
+            };
+            if let Some(Command::ApplyRules { dry_run: false }) = cli.command {
+                print_rule_matches(greader.apply_rules(false).map_err(|error| error.into()));
+                return;
+            }
             let mut ui = UI::new();
-            ui.create(greader, config);
+            ui.create(greader, config, profile);
         }
         Err(error) => println!(
-            "Something went wrong while reading config.toml file:\n{:#}",
+            "Something went wrong while reading {}:\n{:#}",
+            path.display(),
             error
         ),
     }
 }
+
+fn print_rule_matches(matches: Result<Vec<RuleMatch>, Box<dyn Error>>) {
+    match matches {
+        Ok(matches) => {
+            for rule_match in &matches {
+                println!(
+                    "{} | {} [{}] -> {}",
+                    rule_match.target.feed_title,
+                    rule_match.target.title,
+                    rule_match.rules.join(", "),
+                    rule_match.actions.describe()
+                );
+            }
+            println!("{} articles matched", matches.len());
+        }
+        Err(error) => println!("Something went wrong while applying rules:\n{:#}", error),
+    }
+}
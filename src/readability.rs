@@ -0,0 +1,261 @@
+//! Main content extraction for articles whose feeds only ship a summary,
+//! loosely following the scoring used by Mozilla's Readability.
+
+use html5ever::serialize::{serialize, SerializeOpts, TraversalScope};
+use html5ever::tendril::TendrilSink;
+use html5ever::{local_name, parse_document};
+use markup5ever_rcdom::{Handle, Node, NodeData, RcDom, SerializableHandle};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Extracted content shorter than this is most likely a cookie banner or an
+/// error page rather than the article.
+const MIN_CONTENT_LENGTH: usize = 250;
+const MIN_PARAGRAPH_LENGTH: usize = 25;
+
+const UNLIKELY: &[&str] = &[
+    "banner",
+    "combx",
+    "comment",
+    "community",
+    "cookie",
+    "disqus",
+    "footer",
+    "header",
+    "menu",
+    "modal",
+    "nav",
+    "popup",
+    "promo",
+    "related",
+    "share",
+    "sidebar",
+    "social",
+    "sponsor",
+    "subscribe",
+];
+const MAYBE: &[&str] = &[
+    "and", "article", "body", "column", "content", "main", "post",
+];
+const POSITIVE: &[&str] = &[
+    "article", "blog", "body", "content", "entry", "main", "page", "post", "story", "text",
+];
+const NEGATIVE: &[&str] = &[
+    "ad-", "comment", "foot", "footer", "meta", "nav", "promo", "related", "share", "sidebar",
+    "social", "sponsor", "widget",
+];
+
+/// Returns the HTML of the main content of `html`, or `None` when nothing
+/// that looks like an article was found.
+pub fn extract(html: &str) -> Option<String> {
+    let dom = parse_document(RcDom::default(), Default::default())
+        .from_utf8()
+        .read_from(&mut html.as_bytes())
+        .ok()?;
+    remove_unlikely(&dom.document);
+
+    let mut paragraphs = Vec::new();
+    collect_paragraphs(&dom.document, &mut paragraphs);
+
+    let mut candidates: HashMap<*const Node, (Handle, f64)> = HashMap::new();
+    for paragraph in paragraphs {
+        let text = inner_text(&paragraph);
+        let length = text.trim().chars().count();
+        if length < MIN_PARAGRAPH_LENGTH {
+            continue;
+        }
+        let score = 1.0 + text.matches(',').count() as f64 + (length as f64 / 100.0).min(3.0);
+
+        let parent_node = parent(&paragraph);
+        let grandparent_node = parent_node.as_ref().and_then(parent);
+        for (ancestor, share) in [(parent_node, 1.0), (grandparent_node, 0.5)] {
+            if let Some(ancestor) = ancestor.filter(is_element) {
+                candidates
+                    .entry(Rc::as_ptr(&ancestor))
+                    .or_insert_with(|| (ancestor.clone(), initial_score(&ancestor)))
+                    .1 += score * share;
+            }
+        }
+    }
+
+    for (node, score) in candidates.values_mut() {
+        *score *= 1.0 - link_density(node);
+    }
+    let (top, top_score) = candidates
+        .values()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .cloned()?;
+
+    // Content is often split into several siblings, e.g. one div per section
+    let threshold = (top_score * 0.2).max(10.0);
+    let siblings = match parent(&top) {
+        Some(parent) => parent.children.borrow().clone(),
+        None => vec![top.clone()],
+    };
+    let mut content = String::new();
+    let mut text_length = 0;
+    for sibling in siblings {
+        let append = if Rc::ptr_eq(&sibling, &top) {
+            true
+        } else if let Some((_, score)) = candidates.get(&Rc::as_ptr(&sibling)) {
+            *score >= threshold
+        } else if tag_name(&sibling).as_deref() == Some("p") {
+            let length = inner_text(&sibling).trim().chars().count();
+            length > 80 && link_density(&sibling) < 0.25
+        } else {
+            false
+        };
+        if append {
+            text_length += inner_text(&sibling).trim().chars().count();
+            content.push_str(&to_html(&sibling)?);
+        }
+    }
+
+    if text_length < MIN_CONTENT_LENGTH {
+        None
+    } else {
+        Some(content)
+    }
+}
+
+fn tag_name(node: &Handle) -> Option<String> {
+    match node.data {
+        NodeData::Element { ref name, .. } => Some(name.local.to_string()),
+        _ => None,
+    }
+}
+
+fn is_element(node: &Handle) -> bool {
+    matches!(node.data, NodeData::Element { .. })
+}
+
+fn attribute(node: &Handle, attribute: &str) -> String {
+    match node.data {
+        NodeData::Element { ref attrs, .. } => attrs
+            .borrow()
+            .iter()
+            .find(|a| &*a.name.local == attribute)
+            .map(|a| a.value.to_lowercase())
+            .unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+fn parent(node: &Handle) -> Option<Handle> {
+    let weak = node.parent.take();
+    let parent = weak.as_ref().and_then(|p| p.upgrade());
+    node.parent.set(weak);
+    parent
+}
+
+fn inner_text(node: &Handle) -> String {
+    let mut text = String::new();
+    append_text(node, &mut text);
+    text
+}
+
+fn append_text(node: &Handle, text: &mut String) {
+    match node.data {
+        NodeData::Text { ref contents } => text.push_str(&contents.borrow()),
+        _ => {
+            for child in node.children.borrow().iter() {
+                append_text(child, text);
+            }
+        }
+    }
+}
+
+fn link_density(node: &Handle) -> f64 {
+    let length = inner_text(node).chars().count();
+    if length == 0 {
+        return 0.0;
+    }
+    let mut links = Vec::new();
+    collect_by_tag(node, &local_name!("a"), &mut links);
+    let link_length: usize = links.iter().map(|a| inner_text(a).chars().count()).sum();
+    link_length as f64 / length as f64
+}
+
+fn collect_by_tag(node: &Handle, tag: &html5ever::LocalName, found: &mut Vec<Handle>) {
+    for child in node.children.borrow().iter() {
+        if let NodeData::Element { ref name, .. } = child.data {
+            if name.local == *tag {
+                found.push(child.clone());
+            }
+        }
+        collect_by_tag(child, tag, found);
+    }
+}
+
+fn collect_paragraphs(node: &Handle, found: &mut Vec<Handle>) {
+    for child in node.children.borrow().iter() {
+        if let Some("p" | "pre" | "td" | "blockquote") = tag_name(child).as_deref() {
+            found.push(child.clone());
+        }
+        collect_paragraphs(child, found);
+    }
+}
+
+fn class_and_id(node: &Handle) -> String {
+    format!("{} {}", attribute(node, "class"), attribute(node, "id"))
+}
+
+fn class_weight(node: &Handle) -> f64 {
+    let class_and_id = class_and_id(node);
+    let mut weight = 0.0;
+    if POSITIVE.iter().any(|w| class_and_id.contains(w)) {
+        weight += 25.0;
+    }
+    if NEGATIVE.iter().any(|w| class_and_id.contains(w)) {
+        weight -= 25.0;
+    }
+    weight
+}
+
+fn initial_score(node: &Handle) -> f64 {
+    let tag_score = match tag_name(node).as_deref() {
+        Some("article") => 10.0,
+        Some("div") => 5.0,
+        Some("pre" | "td" | "blockquote") => 3.0,
+        Some("address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form") => -3.0,
+        Some("h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th") => -5.0,
+        _ => 0.0,
+    };
+    tag_score + class_weight(node)
+}
+
+/// Drops elements that never hold the article text, like scripts and
+/// navigation, so they can't dilute the scores.
+fn remove_unlikely(node: &Handle) {
+    node.children.borrow_mut().retain(|child| {
+        let tag = match tag_name(child) {
+            Some(tag) => tag,
+            None => return !matches!(child.data, NodeData::Comment { .. }),
+        };
+        if matches!(
+            tag.as_str(),
+            "script" | "style" | "noscript" | "nav" | "aside" | "footer" | "form" | "iframe"
+        ) {
+            return false;
+        }
+        if tag == "body" || tag == "html" || tag == "article" || tag == "main" {
+            return true;
+        }
+        let class_and_id = class_and_id(child);
+        !UNLIKELY.iter().any(|w| class_and_id.contains(w))
+            || MAYBE.iter().any(|w| class_and_id.contains(w))
+    });
+    for child in node.children.borrow().iter() {
+        remove_unlikely(child);
+    }
+}
+
+fn to_html(node: &Handle) -> Option<String> {
+    let mut html = Vec::new();
+    let opts = SerializeOpts {
+        traversal_scope: TraversalScope::IncludeNode,
+        ..Default::default()
+    };
+    serialize(&mut html, &SerializableHandle::from(node.clone()), opts).ok()?;
+    String::from_utf8(html).ok()
+}
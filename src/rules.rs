@@ -0,0 +1,295 @@
+//! Kill-file style rules from `[[rules]]` which mark, hide or tag articles.
+
+use crate::config::Rule;
+use crate::utils::SECONDS_IN_DAY;
+use regex::Regex;
+
+/// What happens to an article matched by one or more rules.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Actions {
+    pub mark_read: bool,
+    pub star: bool,
+    pub hide: bool,
+    pub highlight: bool,
+    pub tags: Vec<String>,
+}
+
+impl Actions {
+    fn merge(&mut self, other: &Actions) {
+        self.mark_read |= other.mark_read;
+        self.star |= other.star;
+        self.hide |= other.hide;
+        self.highlight |= other.highlight;
+        for tag in &other.tags {
+            if !self.tags.contains(tag) {
+                self.tags.push(tag.clone());
+            }
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let mut actions = Vec::new();
+        for (enabled, name) in [
+            (self.mark_read, "mark read"),
+            (self.star, "star"),
+            (self.hide, "hide"),
+            (self.highlight, "highlight"),
+        ] {
+            if enabled {
+                actions.push(name.to_string());
+            }
+        }
+        actions.extend(self.tags.iter().map(|tag| format!("tag {}", tag)));
+        actions.join(", ")
+    }
+}
+
+/// The article fields rules are matched against.
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub article_id: String,
+    pub title: String,
+    pub content: String,
+    pub author: String,
+    pub pub_date: i64,
+    pub feed_id: String,
+    pub feed_title: String,
+    pub category_id: String,
+    pub category_label: String,
+    pub unread: bool,
+    pub starred: bool,
+}
+
+/// An article together with the rules it matched.
+#[derive(Debug, Clone)]
+pub struct RuleMatch {
+    pub target: Target,
+    pub rules: Vec<String>,
+    pub actions: Actions,
+}
+
+#[derive(Debug, Clone)]
+struct CompiledRule {
+    name: String,
+    feed: Option<String>,
+    category: Option<String>,
+    title: Option<Regex>,
+    content: Option<Regex>,
+    author: Option<Regex>,
+    older_than_days: Option<i64>,
+    actions: Actions,
+}
+
+impl CompiledRule {
+    fn matches(&self, target: &Target, now: i64) -> bool {
+        let regex_matches = |regex: &Option<Regex>, text: &str| match regex {
+            Some(regex) => regex.is_match(text),
+            None => true,
+        };
+        self.feed
+            .as_ref()
+            .is_none_or(|feed| *feed == target.feed_id || *feed == target.feed_title)
+            && self.category.as_ref().is_none_or(|category| {
+                *category == target.category_id || *category == target.category_label
+            })
+            && regex_matches(&self.title, &target.title)
+            && regex_matches(&self.content, &target.content)
+            && regex_matches(&self.author, &target.author)
+            && self
+                .older_than_days
+                .is_none_or(|days| target.pub_date < now - days * SECONDS_IN_DAY)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+    rules: Vec<CompiledRule>,
+}
+
+impl Rules {
+    pub fn new(rules: &[Rule]) -> Result<Rules, String> {
+        let compile = |name: &str, pattern: &Option<String>| {
+            pattern
+                .as_deref()
+                .map(Regex::new)
+                .transpose()
+                .map_err(|error| format!("Invalid regex in rule `{}`:\n{}", name, error))
+        };
+        let mut compiled = Vec::new();
+        for (index, rule) in (1..).zip(rules) {
+            let name = rule
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("rule {}", index));
+            compiled.push(CompiledRule {
+                feed: rule.feed.clone(),
+                category: rule.category.clone(),
+                title: compile(&name, &rule.title)?,
+                content: compile(&name, &rule.content)?,
+                author: compile(&name, &rule.author)?,
+                older_than_days: rule.older_than_days,
+                actions: Actions {
+                    mark_read: rule.mark_read,
+                    star: rule.star,
+                    hide: rule.hide,
+                    highlight: rule.highlight,
+                    tags: rule.tags.clone(),
+                },
+                name,
+            });
+        }
+        Ok(Rules { rules: compiled })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Returns the combined actions of every rule matching `target`.
+    pub fn apply(&self, target: Target, now: i64) -> Option<RuleMatch> {
+        let mut names = Vec::new();
+        let mut actions = Actions::default();
+        for rule in self.rules.iter().filter(|rule| rule.matches(&target, now)) {
+            names.push(rule.name.clone());
+            actions.merge(&rule.actions);
+        }
+        if names.is_empty() {
+            None
+        } else {
+            Some(RuleMatch {
+                target,
+                rules: names,
+                actions,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 100 * SECONDS_IN_DAY;
+
+    fn target() -> Target {
+        Target {
+            article_id: String::from("article"),
+            title: String::from("Sponsored: a new phone"),
+            content: String::from("<p>Buy it</p>"),
+            author: String::from("Ads Team"),
+            pub_date: NOW - 2 * SECONDS_IN_DAY,
+            feed_id: String::from("feed/1"),
+            feed_title: String::from("Tech News"),
+            category_id: String::from("user/-/label/Tech"),
+            category_label: String::from("Tech"),
+            unread: true,
+            starred: false,
+        }
+    }
+
+    fn rules(rules: &[Rule]) -> Rules {
+        Rules::new(rules).unwrap()
+    }
+
+    #[test]
+    fn feed_and_category_match_by_id_or_title() {
+        for (feed, category) in [
+            (Some("feed/1"), None),
+            (Some("Tech News"), None),
+            (None, Some("user/-/label/Tech")),
+            (None, Some("Tech")),
+        ] {
+            let rules = rules(&[Rule {
+                feed: feed.map(String::from),
+                category: category.map(String::from),
+                mark_read: true,
+                ..Rule::default()
+            }]);
+            assert!(
+                rules.apply(target(), NOW).is_some(),
+                "{:?} {:?}",
+                feed,
+                category
+            );
+        }
+
+        let rules = rules(&[Rule {
+            feed: Some(String::from("Other News")),
+            mark_read: true,
+            ..Rule::default()
+        }]);
+        assert!(rules.apply(target(), NOW).is_none());
+    }
+
+    #[test]
+    fn older_than_days_compares_the_publication_date() {
+        let older_than = |days| {
+            rules(&[Rule {
+                older_than_days: Some(days),
+                hide: true,
+                ..Rule::default()
+            }])
+        };
+        assert!(older_than(1).apply(target(), NOW).is_some());
+        assert!(older_than(2).apply(target(), NOW).is_none());
+        assert!(older_than(3).apply(target(), NOW).is_none());
+    }
+
+    #[test]
+    fn every_condition_of_a_rule_has_to_match() {
+        let rules = rules(&[Rule {
+            title: Some(String::from("^Sponsored")),
+            author: Some(String::from("Editor")),
+            hide: true,
+            ..Rule::default()
+        }]);
+        assert!(rules.apply(target(), NOW).is_none());
+    }
+
+    #[test]
+    fn actions_of_matching_rules_are_merged() {
+        let rules = rules(&[
+            Rule {
+                name: Some(String::from("ads")),
+                title: Some(String::from("(?i)sponsored")),
+                mark_read: true,
+                tags: vec![String::from("ads")],
+                ..Rule::default()
+            },
+            Rule {
+                content: Some(String::from("nothing like this")),
+                star: true,
+                ..Rule::default()
+            },
+            Rule {
+                feed: Some(String::from("Tech News")),
+                hide: true,
+                tags: vec![String::from("ads"), String::from("tech")],
+                ..Rule::default()
+            },
+        ]);
+
+        let rule_match = rules.apply(target(), NOW).unwrap();
+        assert_eq!(rule_match.rules, vec!["ads", "rule 3"]);
+        assert_eq!(
+            rule_match.actions,
+            Actions {
+                mark_read: true,
+                hide: true,
+                tags: vec![String::from("ads"), String::from("tech")],
+                ..Actions::default()
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_regex_names_the_rule() {
+        let error = Rules::new(&[Rule {
+            name: Some(String::from("broken")),
+            title: Some(String::from("(")),
+            ..Rule::default()
+        }])
+        .unwrap_err();
+        assert!(error.starts_with("Invalid regex in rule `broken`"));
+    }
+}
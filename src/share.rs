@@ -0,0 +1,195 @@
+//! Sending articles to read-it-later services, webhooks and commands.
+
+use crate::config::ShareTarget;
+use chrono::{Local, TimeZone};
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const DEFAULT_WEBHOOK_BODY: &str = r#"{"url": "{link}", "title": "{title}"}"#;
+
+/// Article fields available to share targets.
+#[derive(Debug, Clone)]
+pub struct SharedArticle {
+    pub id: String,
+    pub title: String,
+    pub link: String,
+    pub feed: String,
+    pub author: String,
+    pub pub_date: i64,
+}
+
+impl SharedArticle {
+    fn fields(&self) -> [(&'static str, String); 6] {
+        let date = Local.timestamp_opt(self.pub_date, 0).unwrap().to_rfc3339();
+        [
+            ("id", self.id.clone()),
+            ("title", self.title.clone()),
+            ("link", self.link.clone()),
+            ("feed", self.feed.clone()),
+            ("author", self.author.clone()),
+            ("date", date),
+        ]
+    }
+}
+
+#[derive(Deserialize)]
+struct WallabagToken {
+    access_token: String,
+}
+
+/// Sends the article to `target`. This blocks on the network or the
+/// command, so the UI runs it on its own thread.
+pub fn send(target: &ShareTarget, article: &SharedArticle) -> Result<(), String> {
+    match target {
+        ShareTarget::Wallabag {
+            url,
+            client_id,
+            client_secret,
+            username,
+            password,
+            tags,
+        } => {
+            let url = url.trim_end_matches('/');
+            let output = curl(&[
+                ("request", "POST"),
+                ("url", &format!("{}/oauth/v2/token", url)),
+                ("data-urlencode", "grant_type=password"),
+                ("data-urlencode", &format!("client_id={}", client_id)),
+                (
+                    "data-urlencode",
+                    &format!("client_secret={}", client_secret),
+                ),
+                ("data-urlencode", &format!("username={}", username)),
+                ("data-urlencode", &format!("password={}", password)),
+            ])?;
+            let token: WallabagToken = serde_json::from_slice(&output.stdout)
+                .map_err(|error| format!("Unexpected Wallabag token response: {}", error))?;
+            let authorization = format!("Authorization: Bearer {}", token.access_token);
+            let link = format!("url={}", article.link);
+            let title = format!("title={}", article.title);
+            let tags = format!("tags={}", tags.join(","));
+            let entries = format!("{}/api/entries.json", url);
+            let mut options = vec![
+                ("request", "POST"),
+                ("url", entries.as_str()),
+                ("header", &authorization),
+                ("data-urlencode", &link),
+                ("data-urlencode", &title),
+            ];
+            if tags.len() > "tags=".len() {
+                options.push(("data-urlencode", &tags));
+            }
+            curl(&options)?;
+            Ok(())
+        }
+        ShareTarget::Pocket {
+            url,
+            consumer_key,
+            access_token,
+        } => {
+            let body = serde_json::json!({
+                "url": article.link,
+                "title": article.title,
+                "consumer_key": consumer_key,
+                "access_token": access_token,
+            });
+            curl(&[
+                ("request", "POST"),
+                ("url", url),
+                ("header", "Content-Type: application/json; charset=UTF-8"),
+                ("header", "X-Accept: application/json"),
+                ("data-raw", &body.to_string()),
+            ])?;
+            Ok(())
+        }
+        ShareTarget::Webhook { url, body, headers } => {
+            let body = render_template(body.as_deref().unwrap_or(DEFAULT_WEBHOOK_BODY), article);
+            let mut options = vec![
+                ("request", "POST"),
+                ("url", url.as_str()),
+                ("header", "Content-Type: application/json"),
+            ];
+            for header in headers {
+                options.push(("header", header));
+            }
+            options.push(("data-raw", &body));
+            curl(&options)?;
+            Ok(())
+        }
+        ShareTarget::Command { command } => {
+            let mut process = Command::new("sh");
+            process.arg("-c").arg(command);
+            for (name, value) in article.fields() {
+                process.env(format!("NEWS_RSS_{}", name.to_uppercase()), value);
+            }
+            let output = process
+                .output()
+                .map_err(|error| format!("Couldn't run `{}`: {}", command, error))?;
+            check(output).map(|_| ())
+        }
+    }
+}
+
+/// Replaces the placeholders with the fields escaped for use inside JSON
+/// strings, in one pass so placeholders inside the fields stay as they are.
+fn render_template(template: &str, article: &SharedArticle) -> String {
+    let fields = article.fields();
+    let placeholder = Regex::new(r"\{(\w+)\}").unwrap();
+    placeholder
+        .replace_all(template, |captures: &Captures| {
+            match fields.iter().find(|(name, _)| *name == &captures[1]) {
+                Some((_, value)) => {
+                    let escaped = serde_json::to_string(value).unwrap();
+                    escaped[1..escaped.len() - 1].to_string()
+                }
+                None => captures[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// Runs curl with the options given as a config file on stdin, so secrets
+/// don't show up in the process list.
+fn curl(options: &[(&str, &str)]) -> Result<Output, String> {
+    let mut config = String::new();
+    for (option, value) in options {
+        let value = value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r")
+            .replace('\t', "\\t");
+        config.push_str(&format!("{} = \"{}\"\n", option, value));
+    }
+    let mut child = Command::new("curl")
+        .args(["-s", "-S", "-f", "-L", "-K", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("Couldn't run curl: {}", error))?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(config.as_bytes())
+        .map_err(|error| format!("Couldn't run curl: {}", error))?;
+    let output = child
+        .wait_with_output()
+        .map_err(|error| format!("Couldn't run curl: {}", error))?;
+    check(output)
+}
+
+fn check(output: Output) -> Result<Output, String> {
+    if output.status.success() {
+        Ok(output)
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        match stderr.trim().lines().last() {
+            Some(line) => Err(line.to_string()),
+            None => Err(output.status.to_string()),
+        }
+    }
+}
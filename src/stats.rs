@@ -0,0 +1,170 @@
+//! Reading statistics built from the local read events.
+
+use crate::db::{FeedReadStats, DB};
+use crate::utils::SECONDS_IN_DAY;
+use chrono::{Duration, Local, TimeZone};
+use serde::Serialize;
+use std::error::Error;
+use std::fmt::Write;
+
+const DAYS: i64 = 14;
+const WEEKS: i64 = 8;
+const MOST_IGNORED: usize = 10;
+/// Feeds need this many stored articles before they count as ignored.
+const MIN_ARTICLES: i64 = 5;
+
+/// Feeds without posts for this many months are listed as dead.
+pub const DEAD_AFTER_MONTHS: i64 = 6;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Period {
+    pub period: String,
+    pub read: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    pub per_day: Vec<Period>,
+    pub per_week: Vec<Period>,
+    pub feeds: Vec<FeedReadStats>,
+    /// Feeds with the lowest read ratio first
+    pub most_ignored: Vec<FeedReadStats>,
+    /// Feeds without posts in `dead_after_months`, oldest first
+    pub dead_feeds: Vec<FeedReadStats>,
+    pub dead_after_months: i64,
+}
+
+impl Stats {
+    pub fn collect(db: &DB, dead_after_months: i64) -> Result<Stats, Box<dyn Error>> {
+        let today = Local::now().date_naive();
+        let day_start = |date: chrono::NaiveDate| {
+            Local
+                .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+                .earliest()
+                .map_or(0, |date| date.timestamp())
+        };
+        let days_since = day_start(today - Duration::days(DAYS - 1));
+        let weeks_since = day_start(today - Duration::weeks(WEEKS - 1));
+
+        let per_day = fill_days(
+            db.get_reads_per_period("%Y-%m-%d", days_since)?,
+            today,
+            DAYS,
+        );
+        let per_week = db
+            .get_reads_per_period("%Y-W%W", weeks_since)?
+            .into_iter()
+            .map(|(period, read)| Period { period, read })
+            .collect();
+
+        let feeds = db.get_feed_read_stats()?;
+        let mut most_ignored: Vec<FeedReadStats> = feeds
+            .iter()
+            .filter(|feed| feed.articles >= MIN_ARTICLES)
+            .cloned()
+            .collect();
+        most_ignored.sort_by(|a, b| {
+            a.read_ratio()
+                .total_cmp(&b.read_ratio())
+                .then(b.articles.cmp(&a.articles))
+        });
+        most_ignored.truncate(MOST_IGNORED);
+
+        let dead_before = Local::now().timestamp() - dead_after_months * 30 * SECONDS_IN_DAY;
+        let mut dead_feeds: Vec<FeedReadStats> = feeds
+            .iter()
+            .filter(|feed| feed.last_post.is_none_or(|date| date < dead_before))
+            .cloned()
+            .collect();
+        dead_feeds.sort_by_key(|feed| feed.last_post);
+
+        Ok(Stats {
+            per_day,
+            per_week,
+            feeds,
+            most_ignored,
+            dead_feeds,
+            dead_after_months,
+        })
+    }
+
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        let bars = |report: &mut String, periods: &[Period]| {
+            let max = periods.iter().map(|p| p.read).max().unwrap_or(0).max(1);
+            for period in periods {
+                let bar = "#".repeat((period.read * 40 / max) as usize);
+                let line = format!("  {:<10} {:>4} {}", period.period, period.read, bar);
+                writeln!(report, "{}", line.trim_end()).unwrap();
+            }
+        };
+
+        report.push_str("Read per day\n");
+        bars(&mut report, &self.per_day);
+        report.push_str("\nRead per week\n");
+        bars(&mut report, &self.per_week);
+
+        report.push_str("\nMost ignored feeds\n");
+        if self.most_ignored.is_empty() {
+            report.push_str("  none\n");
+        }
+        for feed in &self.most_ignored {
+            writeln!(
+                report,
+                "  {:>3.0}% {} ({} of {})",
+                feed.read_ratio() * 100.0,
+                feed.title,
+                feed.read,
+                feed.articles
+            )
+            .unwrap();
+        }
+
+        writeln!(report, "\nNo posts in {} months", self.dead_after_months).unwrap();
+        if self.dead_feeds.is_empty() {
+            report.push_str("  none\n");
+        }
+        for feed in &self.dead_feeds {
+            let last_post = feed.last_post.map_or(String::from("never"), |date| {
+                Local
+                    .timestamp_opt(date, 0)
+                    .unwrap()
+                    .format("%Y-%m-%d")
+                    .to_string()
+            });
+            writeln!(report, "  {} (last post {})", feed.title, last_post).unwrap();
+        }
+
+        report.push_str("\nRead ratio per feed\n");
+        for feed in &self.feeds {
+            writeln!(
+                report,
+                "  {:>3.0}% {} ({} of {}, {} min reading)",
+                feed.read_ratio() * 100.0,
+                feed.title,
+                feed.read,
+                feed.articles,
+                feed.reading_seconds / 60
+            )
+            .unwrap();
+        }
+        report
+    }
+}
+
+/// Adds the days without reads so the chart shows gaps.
+fn fill_days(reads: Vec<(String, i64)>, today: chrono::NaiveDate, days: i64) -> Vec<Period> {
+    (0..days)
+        .rev()
+        .map(|days_ago| {
+            let period = (today - Duration::days(days_ago))
+                .format("%Y-%m-%d")
+                .to_string();
+            let read = reads
+                .iter()
+                .find(|(day, _)| *day == period)
+                .map_or(0, |(_, read)| *read);
+            Period { period, read }
+        })
+        .collect()
+}
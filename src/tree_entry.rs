@@ -1,10 +1,23 @@
 use std::fmt;
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    Category,
+    #[default]
+    Feed,
+    /// Unread articles matching `[[highlights]]` or highlighted by rules
+    Highlighted,
+    /// Container of the `Tag` entries
+    Tags,
+    Tag,
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct TreeEntry {
     pub title: String,
     pub id: String,
     pub unread_count: Option<i64>,
+    pub kind: EntryKind,
 }
 
 impl fmt::Display for TreeEntry {
@@ -1,14 +1,26 @@
 use crate::article::Article;
-use crate::config::Config;
-use crate::db::DB;
+use crate::article_body::ArticleBody;
+use crate::clipboard;
+use crate::config::{Config, Highlight, LayoutMode, Profile, ShareTarget};
+use crate::db::{ReadEvent, DB};
+use crate::downloads::DownloadQueue;
+use crate::enclosure::Enclosure;
+use crate::export;
 use crate::greader::{Category, Greader};
-use crate::tree_entry::TreeEntry;
+use crate::highlight::Highlighter;
+use crate::images;
+use crate::readability;
+use crate::rules::Rules;
+use crate::share::{self, SharedArticle};
+use crate::stats::{self, Stats};
+use crate::tree_entry::{EntryKind, TreeEntry};
 use crate::utils;
+use cursive::event::Event;
 use cursive::theme::{BaseColor, BorderStyle, Color, Effect, Palette, Style};
 use cursive::traits::With;
 use cursive::utils::markup::StyledString;
 use cursive::utils::span::SpannedString;
-use cursive::views::{DummyView, OnEventView, TextView};
+use cursive::views::{DummyView, EditView, NamedView, OnEventView, ScrollView, TextView};
 use cursive::{
     traits::*,
     views::{Dialog, LinearLayout, Panel, SelectView},
@@ -17,18 +29,94 @@ use cursive::{
 use ellipse::Ellipse;
 
 use cursive_tree_view::{Placement, TreeView};
-use html2text::render::text_renderer::{RichAnnotation, TaggedLine, TextDecorator};
-use linkify::LinkFinder;
+use std::collections::{BTreeSet, HashMap};
+use std::process::Stdio;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicU64};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use url::Url;
+
+mod keymap;
+mod preview;
+
+use keymap::Scope;
 
 pub struct UI {
     siv: CursiveRunnable,
 }
 
+type TreePanel = Panel<OnEventView<ScrollView<NamedView<TreeView<TreeEntry>>>>>;
+
 #[derive(Clone)]
 struct UserData {
     category_list: Vec<Category>,
     greader: Greader,
     browser: Option<String>,
+    /// Command to run in the foreground once the UI has been suspended
+    foreground_command: Option<Vec<String>>,
+    config: Config,
+    profile: Profile,
+    downloads: DownloadQueue,
+    highlighter: Highlighter,
+    /// Whether the title or text of each article matches the highlights, by
+    /// article id, as matching the text parses the article HTML
+    highlight_matches: HashMap<String, bool>,
+    /// Article open in the reader and since when, for the reading stats
+    reading: Option<(String, Instant)>,
+    sort: SortOrder,
+    /// Shares sent on background threads and not finished yet
+    pending_shares: usize,
+    /// Bumped for every status message so older timers don't clear newer ones
+    status_generation: u64,
+    /// Whether the preview pane is shown on wide enough screens, shared with
+    /// `preview::Responsive`
+    panes: Arc<AtomicBool>,
+    /// Bumped for every article shown in the preview so older mark read
+    /// timers give up
+    preview_generation: Arc<AtomicU64>,
+    /// Rows of the tree showing unread counts, collected again whenever the
+    /// tree is built or rows move
+    tree_rows: Option<TreeRows>,
+    /// What the content panel lists, e.g. `tag rust`, for the status bar
+    filter: Option<String>,
+    /// Connection for the status bar, opened on first use and dropped when
+    /// the profile changes
+    status_db: Option<Rc<DB>>,
+}
+
+/// Order of the content panel, articles come newest first from the database.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum SortOrder {
+    #[default]
+    NewestFirst,
+    OldestFirst,
+}
+
+impl SortOrder {
+    fn label(self) -> &'static str {
+        match self {
+            SortOrder::NewestFirst => "newest first",
+            SortOrder::OldestFirst => "oldest first",
+        }
+    }
+}
+
+/// How a status bar message is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// Status bar messages are cleared after this long.
+const STATUS_TIMEOUT: Duration = Duration::from_secs(6);
+
+impl Default for UI {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl UI {
@@ -38,13 +126,32 @@ impl UI {
         }
     }
 
-    pub fn create(&mut self, greader: Greader, config: Config) {
-        let db = DB::new();
+    pub fn create(&mut self, greader: Greader, config: Config, profile: Profile) {
+        let db = DB::new(&profile.db_path);
         let category_list = db.get_categories().unwrap();
+        let tree_title = tree_panel_title(&config, &profile);
+        let downloads = DownloadQueue::start(self.siv.cb_sink().clone(), refresh_status_info);
+        let highlighter = Highlighter::new(&config.highlights).unwrap_or_default();
+        let panes = Arc::new(AtomicBool::new(config.layout.mode == LayoutMode::Panes));
         self.siv.set_user_data(UserData {
             category_list,
             greader,
-            browser: config.browser,
+            browser: config.browser.clone(),
+            foreground_command: None,
+            config,
+            profile,
+            downloads,
+            highlighter,
+            highlight_matches: HashMap::new(),
+            reading: None,
+            sort: SortOrder::default(),
+            pending_shares: 0,
+            status_generation: 0,
+            panes: Arc::clone(&panes),
+            preview_generation: Arc::default(),
+            tree_rows: None,
+            filter: None,
+            status_db: None,
         });
 
         self.siv.set_theme(cursive::theme::Theme {
@@ -71,126 +178,383 @@ impl UI {
 
         let mut tree = TreeView::<TreeEntry>::new();
         tree.set_on_collapse(tree_on_collapse);
-        tree.set_on_submit(move |siv: &mut Cursive, row| {
-            let db = DB::new();
-            let value = siv.call_on_name("tree", move |tree: &mut TreeView<TreeEntry>| {
-                tree.borrow_item(row).unwrap().clone()
-            });
-            if let Some(v) = value {
-                let articles = db.get_articles_for_feed(&v.id).unwrap();
-
-                // FIXME: Find a way how to update feed unread count when the article was read from
-                // focused category
-                let len = articles
-                    .iter()
-                    .filter(|a| a.unread())
-                    .collect::<Vec<_>>()
-                    .len();
-
-                siv.call_on_name("tree", move |tree: &mut TreeView<TreeEntry>| {
-                    let item = tree.borrow_item_mut(row).unwrap();
-                    item.unread_count = Some(len.try_into().unwrap());
-                });
-
-                draw_articles(articles, siv, &v.title);
-            }
-        });
+        tree.set_on_submit(open_tree_row);
 
         let cat_list = self
             .siv
             .with_user_data(|user_data: &mut UserData| user_data.category_list.clone())
             .unwrap();
-        build_tree(cat_list, &mut tree);
-
-        self.siv.set_global_callback('R', move |siv| {
-            let user_data = siv
-                .with_user_data(|user_data: &mut UserData| user_data.clone())
-                .unwrap();
-
-            user_data.greader.sync().unwrap();
-
-            siv.call_on_name("tree", |tree: &mut TreeView<TreeEntry>| {
-                /* let selected_row = tree.row().unwrap();
-                let item = tree.borrow_item_mut(selected_row).unwrap();
-                item.unread_count = Some(0); */
-                tree.clear();
-                build_tree(user_data.category_list, tree);
-                // tree.set_collapsed(selected_row, false);
-                // tree.set_selected_row(selected_row);
-            });
-            siv.focus_name("tree").unwrap();
-            // siv.pop_layer();
-            // TODO: show progress bar
-            // TODO: redraw the panel with tree itrems
-            // TODO: redraw the content section
-        });
+        let highlighted = highlighted_count(&mut self.siv, &db);
+        let tree_rows = build_tree(&db, cat_list, highlighted, &mut tree);
+        self.siv
+            .with_user_data(|user_data: &mut UserData| user_data.tree_rows = Some(tree_rows));
 
-        self.siv.set_global_callback('h', |siv| {
-            if siv.screen().len() > 1 {
-                siv.pop_layer();
-            }
-        });
+        keymap::install_global(&mut self.siv);
 
         let mut select = SelectView::<Article>::new();
         select.set_on_submit(content_on_submit);
+        select.set_on_select(|siv, _| preview::show_selected(siv));
 
-        self.siv.add_fullscreen_layer(
-            LinearLayout::horizontal()
-                .child(
-                    Panel::new(
-                        OnEventView::new(tree.with_name("tree").scrollable())
-                            .on_event('j', |s| {
-                                s.call_on_name("tree", move |tree: &mut TreeView<TreeEntry>| {
-                                    tree.focus_down(1);
-                                });
-                            })
-                            .on_event('k', |s| {
-                                s.call_on_name("tree", move |tree: &mut TreeView<TreeEntry>| {
-                                    tree.focus_up(1);
-                                });
-                            }),
+        let main_layout = LinearLayout::horizontal()
+            .child(
+                Panel::new(
+                    OnEventView::new(tree.with_name("tree").scrollable())
+                        .with(|view| keymap::install(view, Scope::Tree)),
+                )
+                .title(tree_title)
+                .with_name("tree_panel")
+                .full_height()
+                .max_width(40)
+                .min_width(20),
+            )
+            .child(
+                Dialog::new()
+                    .content(
+                        OnEventView::new(select.with_name("content").scrollable())
+                            .with(|view| keymap::install(view, Scope::Content)),
                     )
-                    .title("Feed list")
-                    .with_name("tree_panel")
+                    .title("Content bar")
+                    .with_name("panel")
                     .full_height()
-                    .max_width(40)
-                    .min_width(20),
-                )
-                .child(
-                    Dialog::new()
-                        .content(
-                            OnEventView::new(select.with_name("content").scrollable())
-                                .on_event('j', content_select_down)
-                                .on_event('k', content_select_up)
-                                .on_event('s', sort_asc)
-                                .on_event('S', sort_desc)
-                                .on_event('o', open_article)
-                                .on_event('N', toggle_article_read),
-                        )
-                        .title("Content bar")
-                        .with_name("panel")
-                        .full_height()
-                        .full_width(),
-                ),
+                    .full_width(),
+            )
+            .child(preview::pane());
+        let min_width = self
+            .siv
+            .with_user_data(|user_data: &mut UserData| user_data.config.layout.min_width())
+            .unwrap();
+        let main_layout =
+            preview::Responsive::new(main_layout, min_width, panes, self.siv.cb_sink().clone());
+
+        self.siv.add_fullscreen_layer(
+            LinearLayout::vertical().child(main_layout).child(
+                LinearLayout::horizontal()
+                    .child(TextView::new("").with_name("status").full_width())
+                    .child(TextView::new("").with_name("status_info")),
+            ),
+        );
+        refresh_status_info(&mut self.siv);
+
+        // Terminal browsers need the screen, so the event loop is left while
+        // they run and started again afterwards
+        loop {
+            self.siv.run();
+            let command = self
+                .siv
+                .with_user_data(|user_data: &mut UserData| user_data.foreground_command.take())
+                .flatten();
+            let Some(command) = command else { break };
+            if let Err(error) = std::process::Command::new(&command[0])
+                .args(&command[1..])
+                .status()
+            {
+                set_status(
+                    &mut self.siv,
+                    &format!("Couldn't run `{}`: {}", command[0], error),
+                    Severity::Error,
+                );
+            }
+        }
+        finish_reading(&mut self.siv);
+    }
+}
+
+fn open_db(siv: &mut Cursive) -> DB {
+    let db_path = siv
+        .with_user_data(|user_data: &mut UserData| user_data.profile.db_path.clone())
+        .unwrap();
+    DB::new(&db_path)
+}
+
+fn tree_panel_title(config: &Config, profile: &Profile) -> String {
+    if config.profiles.is_empty() {
+        String::from("Feed list")
+    } else {
+        format!("Feed list [{}]", profile.name)
+    }
+}
+
+fn select_profile(siv: &mut Cursive) {
+    let (profile_names, current) = siv
+        .with_user_data(|user_data: &mut UserData| {
+            (
+                user_data.config.profile_names(),
+                user_data.profile.name.clone(),
+            )
+        })
+        .unwrap();
+    if profile_names.len() < 2 {
+        return;
+    }
+
+    let mut select = SelectView::<String>::new();
+    for name in profile_names {
+        let label = if name == current {
+            format!("* {}", name)
+        } else {
+            format!("  {}", name)
+        };
+        select.add_item(label, name);
+    }
+    select.set_on_submit(|siv: &mut Cursive, name: &String| {
+        siv.pop_layer();
+        switch_profile(siv, name);
+    });
+
+    siv.add_layer(
+        Dialog::around(select.scrollable())
+            .title("Profiles")
+            .dismiss_button("Cancel"),
+    );
+}
+
+fn switch_profile(siv: &mut Cursive, name: &str) {
+    let config = siv
+        .with_user_data(|user_data: &mut UserData| user_data.config.clone())
+        .unwrap();
+    let profile = match config.profile(Some(name)) {
+        Ok(profile) => profile,
+        Err(error) => {
+            siv.add_layer(Dialog::info(format!("{:#}", error)));
+            return;
+        }
+    };
+    let db = DB::new(&profile.db_path);
+    if let Err(error) = db.create_db() {
+        siv.add_layer(Dialog::info(format!("{:#}", error)));
+        return;
+    }
+    let rules = match Rules::new(&config.rules) {
+        Ok(rules) => rules,
+        Err(error) => {
+            set_status(
+                siv,
+                &format!("Rules are not applied: {}", error),
+                Severity::Error,
+            );
+            Rules::default()
+        }
+    };
+    let greader = match Greader::login(&profile) {
+        Ok(greader) => greader.with_rules(rules),
+        Err(error) => {
+            siv.add_layer(Dialog::info(format!("{:#}", error)));
+            return;
+        }
+    };
+    let title = tree_panel_title(&config, &profile);
+
+    siv.with_user_data(|user_data: &mut UserData| {
+        user_data.greader = greader;
+        user_data.profile = profile;
+        user_data.status_db = None;
+        user_data.highlight_matches.clear();
+    });
+
+    reload_tree(siv);
+    siv.call_on_name("tree_panel", |view: &mut TreePanel| {
+        view.set_title(title);
+    });
+}
+
+fn sync(siv: &mut Cursive) {
+    let (greader, retention) = siv
+        .with_user_data(|user_data: &mut UserData| {
+            (
+                user_data.greader.clone(),
+                user_data.config.retention.clone(),
+            )
+        })
+        .unwrap();
+
+    // TODO: show progress bar
+    if let Err(error) = greader.sync() {
+        set_status(siv, &format!("Sync failed: {:#}", error), Severity::Error);
+        return;
+    }
+    let report = open_db(siv).prune(&retention).unwrap();
+
+    reload_tree(siv);
+    if report.articles > 0 {
+        set_status(
+            siv,
+            &format!(
+                "Synced, pruned {} articles and reclaimed {}",
+                report.articles,
+                utils::formatted_size(report.bytes)
+            ),
+            Severity::Info,
         );
+    } else {
+        set_status(siv, "Synced", Severity::Info);
+    }
+}
+
+fn apply_rules(siv: &mut Cursive) {
+    let greader = siv
+        .with_user_data(|user_data: &mut UserData| user_data.greader.clone())
+        .unwrap();
+    match greader.apply_rules(false) {
+        Ok(matches) => {
+            reload_tree(siv);
+            set_status(
+                siv,
+                &format!("Rules matched {} articles", matches.len()),
+                Severity::Info,
+            );
+        }
+        Err(error) => set_status(siv, &format!("{:#}", error), Severity::Error),
+    }
+}
 
-        self.siv.run();
+fn confirm_full_resync(siv: &mut Cursive) {
+    siv.add_layer(
+        Dialog::text("Drop the local cache and fetch everything from the server again?")
+            .title("Full resync")
+            .button("Resync", |s| {
+                s.pop_layer();
+                full_resync(s);
+            })
+            .dismiss_button("Cancel"),
+    );
+}
+
+fn full_resync(siv: &mut Cursive) {
+    let greader = siv
+        .with_user_data(|user_data: &mut UserData| user_data.greader.clone())
+        .unwrap();
+    if let Err(error) = greader.full_resync() {
+        siv.add_layer(Dialog::info(format!("{:#}", error)));
+        return;
     }
+    reload_tree(siv);
+}
+
+/// Re-reads the category list from the database and rebuilds the tree from
+/// scratch, clearing the content panel.
+fn reload_tree(siv: &mut Cursive) {
+    let db = open_db(siv);
+    let category_list = db.get_categories().unwrap();
+    siv.with_user_data(|user_data: &mut UserData| {
+        user_data.category_list = category_list.clone();
+    });
+
+    let highlighted = highlighted_count(siv, &db);
+    let tree_rows = siv.call_on_name("tree", |tree: &mut TreeView<TreeEntry>| {
+        tree.clear();
+        build_tree(&db, category_list, highlighted, tree)
+    });
+    siv.with_user_data(|user_data: &mut UserData| user_data.tree_rows = tree_rows);
+    set_filter(siv, None);
+    draw_articles(vec![], siv, "Content bar");
+    siv.focus_name("tree").unwrap();
+    refresh_status_info(siv);
+}
+
+/// Rebuilds the tree from the database keeping the selected row and the
+/// content panel, e.g. after the set of tags changed.
+fn rebuild_tree(siv: &mut Cursive) {
+    let db = open_db(siv);
+    let category_list = siv
+        .with_user_data(|user_data: &mut UserData| user_data.category_list.clone())
+        .unwrap();
+    let highlighted = highlighted_count(siv, &db);
+    let tree_rows = siv.call_on_name("tree", |tree: &mut TreeView<TreeEntry>| {
+        let row = tree.row();
+        tree.clear();
+        let tree_rows = build_tree(&db, category_list, highlighted, tree);
+        if let Some(row) = row.filter(|row| *row < tree.len()) {
+            tree.set_selected_row(row);
+        }
+        tree_rows
+    });
+    siv.with_user_data(|user_data: &mut UserData| user_data.tree_rows = tree_rows);
+    refresh_status_info(siv);
+}
+
+fn current_highlighter(siv: &mut Cursive) -> Highlighter {
+    siv.with_user_data(|user_data: &mut UserData| user_data.highlighter.clone())
+        .unwrap()
+}
+
+/// Unread articles belonging in the "Highlighted" list, matching only the
+/// articles not seen since the highlights were last set.
+fn highlighted_articles(siv: &mut Cursive, db: &DB) -> Vec<Article> {
+    let articles = db.get_unread_articles().unwrap();
+    siv.with_user_data(|user_data: &mut UserData| {
+        articles
+            .into_iter()
+            .filter(|article| is_highlighted(user_data, article))
+            .collect()
+    })
+    .unwrap()
+}
+
+fn is_highlighted(user_data: &mut UserData, article: &Article) -> bool {
+    article.highlighted
+        || *user_data
+            .highlight_matches
+            .entry(article.id.clone())
+            .or_insert_with(|| article.is_highlighted(&user_data.highlighter))
 }
 
-fn build_tree(cat_list: Vec<Category>, tree: &mut TreeView<TreeEntry>) {
-    let db = DB::new();
+/// Count for the "Highlighted" node, `None` when there is nothing to show
+/// it for.
+fn highlighted_count(siv: &mut Cursive, db: &DB) -> Option<usize> {
+    let highlighted = highlighted_articles(siv, db).len();
+    let configured = siv
+        .with_user_data(|user_data: &mut UserData| !user_data.highlighter.is_empty())
+        .unwrap();
+    (highlighted > 0 || configured).then_some(highlighted)
+}
+
+fn build_tree(
+    db: &DB,
+    cat_list: Vec<Category>,
+    highlighted: Option<usize>,
+    tree: &mut TreeView<TreeEntry>,
+) -> TreeRows {
+    let mut feed_categories = HashMap::new();
     // FIXME: this element is needed purely to properly align tree elements
     tree.insert_item(
         TreeEntry {
             id: String::from("dummy"),
             title: String::from(""),
             unread_count: None,
+            kind: EntryKind::Feed,
         },
         Placement::After,
         0,
     );
 
+    // Inserted before the categories, which all go right after the dummy
+    // row, so the section ends up last
+    let tags = db.get_tags().unwrap();
+    if !tags.is_empty() {
+        tree.insert_container_item(
+            TreeEntry {
+                id: String::from("local/tags"),
+                title: String::from("Tags"),
+                unread_count: None,
+                kind: EntryKind::Tags,
+            },
+            Placement::After,
+            0,
+        );
+        for (tag, unread_count) in tags {
+            tree.insert_item(
+                TreeEntry {
+                    id: format!("user/-/label/{}", tag),
+                    title: tag,
+                    unread_count: Some(unread_count),
+                    kind: EntryKind::Tag,
+                },
+                Placement::LastChild,
+                1,
+            );
+        }
+    }
+
     for category in cat_list {
         let unread_count = db.get_category_unread_count(&category.id).unwrap();
         tree.insert_container_item(
@@ -198,6 +562,7 @@ fn build_tree(cat_list: Vec<Category>, tree: &mut TreeView<TreeEntry>) {
                 id: category.id.clone(),
                 title: category.label.to_string(),
                 unread_count: Some(unread_count),
+                kind: EntryKind::Category,
             },
             Placement::After,
             0,
@@ -205,11 +570,13 @@ fn build_tree(cat_list: Vec<Category>, tree: &mut TreeView<TreeEntry>) {
         let feeds = db.get_feeds_for_category(&category.id).unwrap();
         for feed in feeds {
             let unread_count = db.get_feed_unread_count(feed.id.as_str()).unwrap();
+            feed_categories.insert(feed.id.clone(), category.id.clone());
             tree.insert_item(
                 TreeEntry {
                     id: feed.id,
                     title: feed.title,
                     unread_count: Some(unread_count),
+                    kind: EntryKind::Feed,
                 },
                 Placement::LastChild,
                 1,
@@ -217,58 +584,330 @@ fn build_tree(cat_list: Vec<Category>, tree: &mut TreeView<TreeEntry>) {
         }
     }
 
+    if let Some(highlighted) = highlighted {
+        // Inserted last right after the dummy row so it ends up on top
+        tree.insert_item(
+            TreeEntry {
+                id: String::from("local/highlighted"),
+                title: String::from("Highlighted"),
+                unread_count: Some(highlighted as i64),
+                kind: EntryKind::Highlighted,
+            },
+            Placement::After,
+            0,
+        );
+    }
+
     // FIXME: hack to properly align elements in tree view
     if tree.len() > 1 {
         tree.remove_item(0);
     }
+    TreeRows::new(tree, feed_categories)
+}
+
+/// Lists the articles of the feed, tag or virtual node at `row`.
+fn open_tree_row(siv: &mut Cursive, row: usize) {
+    let db = open_db(siv);
+    let value = siv.call_on_name("tree", move |tree: &mut TreeView<TreeEntry>| {
+        tree.borrow_item(row).unwrap().clone()
+    });
+    if let Some(v) = value {
+        let articles = match v.kind {
+            EntryKind::Highlighted => highlighted_articles(siv, &db),
+            EntryKind::Tag => db.get_articles_for_tag(&v.title).unwrap(),
+            EntryKind::Tags | EntryKind::Category => return,
+            EntryKind::Feed => db.get_articles_for_feed(&v.id).unwrap(),
+        };
+
+        let feed_ids: Vec<String> = articles
+            .iter()
+            .map(|article| article.feed_id.clone())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        update_unread_counts(siv, &feed_ids);
+
+        let filter = match v.kind {
+            EntryKind::Highlighted => String::from("highlighted"),
+            EntryKind::Tag => format!("tag {}", v.title),
+            _ => format!("feed {}", v.title),
+        };
+        set_filter(siv, Some(filter));
+        draw_articles(articles, siv, &v.title);
+    }
+}
+
+fn is_unread_feed(tree: &TreeView<TreeEntry>, row: usize) -> bool {
+    tree.borrow_item(row)
+        .is_some_and(|entry| entry.kind == EntryKind::Feed && entry.unread_count.unwrap_or(0) > 0)
+}
+
+/// First unread feed from `row` up to `end`, or the end of the tree. Only the
+/// collapsed category holding that feed is expanded, the others are folded
+/// back after looking inside.
+fn find_unread_feed(
+    tree: &mut TreeView<TreeEntry>,
+    mut row: usize,
+    end: Option<usize>,
+) -> Option<usize> {
+    while row < end.unwrap_or(tree.len()) {
+        if is_unread_feed(tree, row) {
+            return Some(row);
+        }
+        let entry = tree.borrow_item(row).unwrap();
+        if entry.kind == EntryKind::Category && entry.unread_count.unwrap_or(0) > 0 {
+            let len = tree.len();
+            tree.expand_item(row);
+            let children = tree.len() - len;
+            if children > 0 {
+                match (row + 1..=row + children).find(|child| is_unread_feed(tree, *child)) {
+                    Some(child) => return Some(child),
+                    None => tree.collapse_item(row),
+                }
+            }
+        }
+        row += 1;
+    }
+    None
+}
+
+/// Selects and opens the next feed with unread articles after the selected
+/// row, wrapping around.
+fn next_unread_feed(siv: &mut Cursive) -> bool {
+    let row = siv
+        .call_on_name("tree", |tree: &mut TreeView<TreeEntry>| {
+            let selected = tree
+                .row()
+                .and_then(|row| tree.borrow_item(row).cloned())
+                .map(|entry| (entry.kind, entry.id));
+            let start = (0..tree.len())
+                .find(|row| {
+                    let entry = tree.borrow_item(*row).unwrap();
+                    selected
+                        .as_ref()
+                        .is_some_and(|(kind, id)| entry.kind == *kind && entry.id == *id)
+                })
+                .map_or(0, |row| row + 1);
+            let next = find_unread_feed(tree, start, None)
+                .or_else(|| find_unread_feed(tree, 0, Some(start)));
+            if let Some(row) = next {
+                tree.set_selected_row(row);
+            }
+            next
+        })
+        .flatten();
+    collect_tree_rows(siv);
+    match row {
+        Some(row) => {
+            open_tree_row(siv, row);
+            true
+        }
+        None => false,
+    }
+}
+
+fn next_unread_feed_action(siv: &mut Cursive) {
+    if !next_unread_feed(siv) {
+        set_status(siv, "No unread feeds", Severity::Info);
+    }
+}
+
+/// Index of the first unread article in the content panel after `after`.
+fn next_unread_index(siv: &mut Cursive, after: Option<usize>) -> Option<usize> {
+    siv.call_on_name("content", |view: &mut SelectView<Article>| {
+        let start = after.map_or(0, |index| index + 1);
+        view.iter()
+            .enumerate()
+            .skip(start)
+            .find(|(_, (_, article))| article.unread())
+            .map(|(index, _)| index)
+    })
+    .flatten()
+}
+
+/// Moves to the next unread article, in the next unread feed when the list
+/// has none left. From the reader the article is opened directly and the
+/// current one marked read.
+fn next_unread_article(siv: &mut Cursive) {
+    let reader_open = siv
+        .call_on_name("article_body", |_: &mut ArticleBody| ())
+        .is_some();
+    if reader_open {
+        if let Some(article) = reader_article(siv).filter(|article| article.unread()) {
+            let db = open_db(siv);
+            mark_article_as_read(siv, &article.id, db);
+        }
+    }
+
+    let current = if reader_open { reader_index(siv) } else { None };
+    let selected = current.or_else(|| {
+        siv.call_on_name("content", |view: &mut SelectView<Article>| {
+            view.selected_id()
+        })
+        .flatten()
+    });
+    let mut next = next_unread_index(siv, selected);
+    if next.is_none() && next_unread_feed(siv) {
+        next = next_unread_index(siv, None);
+    }
+    let article = next.and_then(|index| {
+        siv.call_on_name("content", |view: &mut SelectView<Article>| {
+            view.set_selection(index);
+            view.selection()
+        })
+        .flatten()
+    });
+
+    match article {
+        Some(article) if reader_open => {
+            siv.pop_layer();
+            content_on_submit(siv, &article);
+        }
+        Some(_) => {
+            siv.focus_name("content").ok();
+            preview::show_selected(siv);
+        }
+        None => set_status(siv, "No unread articles", Severity::Info),
+    }
+}
+
+fn tree_focus_down(s: &mut Cursive) {
+    s.call_on_name("tree", |tree: &mut TreeView<TreeEntry>| tree.focus_down(1));
+}
+
+fn tree_focus_up(s: &mut Cursive) {
+    s.call_on_name("tree", |tree: &mut TreeView<TreeEntry>| tree.focus_up(1));
 }
 
 fn content_select_down(s: &mut Cursive) {
-    s.call_on_name("content", move |view: &mut SelectView<Article>| {
+    let cb = s.call_on_name("content", move |view: &mut SelectView<Article>| {
         view.select_down(1)
     });
+    if let Some(cb) = cb {
+        cb(s);
+    }
 }
 
 fn content_select_up(s: &mut Cursive) {
-    s.call_on_name("content", move |view: &mut SelectView<Article>| {
+    let cb = s.call_on_name("content", move |view: &mut SelectView<Article>| {
         view.select_up(1)
     });
+    if let Some(cb) = cb {
+        cb(s);
+    }
 }
 
 fn sort_asc(s: &mut Cursive) {
-    s.call_on_name("content", move |view: &mut SelectView<Article>| {
-        view.sort_by(|a1, a2| a1.pub_date.cmp(&a2.pub_date));
-    });
+    set_sort(s, SortOrder::OldestFirst);
 }
 
 fn sort_desc(s: &mut Cursive) {
-    s.call_on_name("content", move |view: &mut SelectView<Article>| {
-        view.sort_by(|a1, a2| a2.pub_date.cmp(&a1.pub_date));
+    set_sort(s, SortOrder::NewestFirst);
+}
+
+/// Sorts the content panel and keeps the order for the next lists.
+fn set_sort(siv: &mut Cursive, sort: SortOrder) {
+    siv.with_user_data(|user_data: &mut UserData| user_data.sort = sort);
+    siv.call_on_name("content", move |view: &mut SelectView<Article>| {
+        sort_articles(view, sort);
     });
+    preview::show_selected(siv);
+    refresh_status_info(siv);
+}
+
+fn sort_articles(view: &mut SelectView<Article>, sort: SortOrder) {
+    match sort {
+        SortOrder::NewestFirst => view.sort_by(|a1, a2| a2.pub_date.cmp(&a1.pub_date)),
+        SortOrder::OldestFirst => view.sort_by(|a1, a2| a1.pub_date.cmp(&a2.pub_date)),
+    }
 }
 
 fn tree_on_collapse(siv: &mut Cursive, row: usize, collapsed: bool, _children: usize) {
+    collect_tree_rows(siv);
     if !collapsed {
-        let db = DB::new();
+        let db = open_db(siv);
         let value = siv.call_on_name("tree", move |tree: &mut TreeView<TreeEntry>| {
             tree.borrow_item(row).unwrap().clone()
         });
-        let v = value.unwrap_or(TreeEntry::default());
+        let v = value.unwrap_or_default();
+        if v.kind != EntryKind::Category {
+            return;
+        }
         let articles = db.get_articles_for_category(&v.id).unwrap();
 
+        set_filter(siv, Some(format!("category {}", v.title)));
         draw_articles(articles, siv, &v.title);
     }
 }
 
+fn show_search(siv: &mut Cursive) {
+    siv.add_layer(
+        Dialog::around(
+            EditView::new()
+                .on_submit(|s, query| {
+                    s.pop_layer();
+                    search(s, query);
+                })
+                .min_width(40),
+        )
+        .title("Search articles")
+        .dismiss_button("Cancel"),
+    );
+}
+
+/// Lists the articles, read ones included, whose title or text contains
+/// `query`, ignoring case.
+fn search(siv: &mut Cursive, query: &str) {
+    let query = query.trim();
+    if query.is_empty() {
+        return;
+    }
+    let matcher = Highlighter::new(&[Highlight {
+        pattern: query.to_string(),
+        ..Highlight::default()
+    }])
+    .unwrap();
+    let articles: Vec<Article> = open_db(siv)
+        .search_articles(query)
+        .unwrap()
+        .into_iter()
+        .filter(|article| {
+            matcher.is_match(&article.title) || matcher.is_match_html(&article.content)
+        })
+        .collect();
+    if articles.is_empty() {
+        set_status(
+            siv,
+            &format!("Nothing found for \"{}\"", query),
+            Severity::Warning,
+        );
+        return;
+    }
+    set_filter(siv, Some(format!("search \"{}\"", query)));
+    draw_articles(articles, siv, &format!("Search: {}", query));
+}
+
+fn set_filter(siv: &mut Cursive, filter: Option<String>) {
+    siv.with_user_data(|user_data: &mut UserData| user_data.filter = filter);
+    refresh_status_info(siv);
+}
+
 fn draw_articles(articles: Vec<Article>, siv: &mut Cursive, title: &str) {
     siv.call_on_name("panel", move |view: &mut Dialog| {
         view.set_title(title);
     });
+    let highlighter = current_highlighter(siv);
+    let sort = siv
+        .with_user_data(|user_data: &mut UserData| user_data.sort)
+        .unwrap();
     let articles_len = siv.call_on_name("content", move |view: &mut SelectView<Article>| {
         view.clear();
         let articles_len = articles.len();
         for article in articles {
-            view.add_item(article.draw(), article);
+            view.add_item(article.draw(&highlighter), article);
+        }
+        if sort != SortOrder::NewestFirst {
+            sort_articles(view, sort);
         }
 
         articles_len
@@ -277,97 +916,320 @@ fn draw_articles(articles: Vec<Article>, siv: &mut Cursive, title: &str) {
     if articles_len.unwrap() > 0 {
         siv.focus_name("content").unwrap();
     }
+    preview::show_selected(siv);
+}
+
+/// Rows of the tree showing unread counts. Rows move whenever a category
+/// is expanded or collapsed, so `collect_tree_rows` runs again then.
+#[derive(Default, Clone)]
+struct TreeRows {
+    /// Feed id to the category holding it, shown or not
+    feed_categories: HashMap<String, String>,
+    /// Feed id to every row showing the feed
+    feeds: HashMap<String, Vec<usize>>,
+    /// Category id to its row
+    categories: HashMap<String, usize>,
+    highlighted: Option<usize>,
+    /// Tag name to its row
+    tags: HashMap<String, usize>,
 }
 
-fn decrease_unread_count(tree: &mut TreeView<TreeEntry>, row: usize) {
-    let item = tree.borrow_item_mut(row).unwrap();
-    if let Some(count) = item.unread_count {
-        if count > 0 {
-            item.unread_count = Some(item.unread_count.unwrap() - 1);
+impl TreeRows {
+    fn new(tree: &TreeView<TreeEntry>, feed_categories: HashMap<String, String>) -> Self {
+        let mut rows = TreeRows {
+            feed_categories,
+            ..TreeRows::default()
+        };
+        for row in 0..tree.len() {
+            let entry = tree.borrow_item(row).unwrap();
+            match entry.kind {
+                // Skips the alignment row, which has no count
+                EntryKind::Feed if entry.unread_count.is_some() => {
+                    rows.feeds.entry(entry.id.clone()).or_default().push(row);
+                }
+                EntryKind::Category => {
+                    rows.categories.insert(entry.id.clone(), row);
+                }
+                EntryKind::Highlighted => rows.highlighted = Some(row),
+                EntryKind::Tag => {
+                    rows.tags.insert(entry.title.clone(), row);
+                }
+                _ => (),
+            }
         }
+        rows
     }
 }
 
-fn increase_unread_count(tree: &mut TreeView<TreeEntry>, row: usize) {
-    let item = tree.borrow_item_mut(row).unwrap();
-    item.unread_count = Some(item.unread_count.unwrap() + 1);
+/// Collects the rows again after categories were expanded or collapsed.
+fn collect_tree_rows(siv: &mut Cursive) {
+    let Some(rows) = siv
+        .with_user_data(|user_data: &mut UserData| user_data.tree_rows.take())
+        .flatten()
+    else {
+        return;
+    };
+    let rows = siv.call_on_name("tree", |tree: &mut TreeView<TreeEntry>| {
+        TreeRows::new(tree, rows.feed_categories)
+    });
+    siv.with_user_data(|user_data: &mut UserData| user_data.tree_rows = rows);
 }
 
-fn toggle_article_read(s: &mut Cursive) {
-    let selected_item = s
-        .call_on_name("content", move |view: &mut SelectView<Article>| {
-            view.selection().unwrap()
-        })
-        .unwrap();
-    let db = DB::new();
-    if selected_item.unread() {
-        mark_article_as_read(s, &selected_item.id, db);
-    } else {
-        mark_article_as_unread(s, &selected_item.id, db);
+fn set_count(tree: &mut TreeView<TreeEntry>, row: usize, count: i64) {
+    if let Some(entry) = tree.borrow_item_mut(row) {
+        entry.unread_count = Some(count);
     }
-
-    content_select_down(s);
 }
 
-fn refresh_selected_article(siv: &mut Cursive, item_id: &str, db: DB) {
-    siv.call_on_name("content", move |view: &mut SelectView<Article>| {
-        let id = view.selected_id().unwrap();
-        view.remove_item(id);
-        let article = db.get_article(item_id.to_string()).unwrap();
-        view.insert_item(id, article.draw(), article.clone());
+fn add_count(tree: &mut TreeView<TreeEntry>, row: usize, delta: i64) {
+    if let Some(entry) = tree.borrow_item_mut(row) {
+        entry.unread_count = Some((entry.unread_count.unwrap_or(0) + delta).max(0));
+    }
+}
 
-        if id == 0 {
-            view.select_up(1);
-        } else {
-            view.select_down(1);
+/// Re-reads the unread counts of the given feeds from the database along
+/// with the categories containing them. Feeds in a collapsed category have
+/// no row, so that category alone is expanded for the update and collapsed
+/// again, which keeps the other rows in place.
+fn update_feed_counts(siv: &mut Cursive, db: &DB, feed_ids: &[String]) {
+    let rows = siv
+        .with_user_data(|user_data: &mut UserData| user_data.tree_rows.take())
+        .flatten()
+        .unwrap_or_default();
+    siv.call_on_name("tree", |tree: &mut TreeView<TreeEntry>| {
+        let mut categories = BTreeSet::new();
+        for feed_id in feed_ids {
+            let count = db.get_feed_unread_count(feed_id).unwrap();
+            let category = rows.feed_categories.get(feed_id);
+            categories.extend(category);
+            if let Some(feed_rows) = rows.feeds.get(feed_id) {
+                for row in feed_rows {
+                    set_count(tree, *row, count);
+                }
+            } else if let Some(row) = category.and_then(|id| rows.categories.get(id)) {
+                let len = tree.len();
+                tree.expand_item(*row);
+                let children = tree.len() - len;
+                for child in *row + 1..=*row + children {
+                    if tree
+                        .borrow_item(child)
+                        .is_some_and(|entry| entry.id == *feed_id)
+                    {
+                        set_count(tree, child, count);
+                    }
+                }
+                if children > 0 {
+                    tree.collapse_item(*row);
+                }
+            }
+        }
+        for category_id in categories {
+            if let Some(row) = rows.categories.get(category_id) {
+                set_count(
+                    tree,
+                    *row,
+                    db.get_category_unread_count(category_id).unwrap(),
+                );
+            }
         }
     });
+    siv.with_user_data(|user_data: &mut UserData| user_data.tree_rows = Some(rows));
 }
 
-fn mark_article_as_read(siv: &mut Cursive, item_id: &str, db: DB) {
-    let greader = siv
-        .with_user_data(|user_data: &mut UserData| user_data.greader.clone())
+/// Re-reads the unread counts of the given feeds, the tags and the
+/// highlighted node, after any number of articles changed.
+fn update_unread_counts(siv: &mut Cursive, feed_ids: &[String]) {
+    let db = open_db(siv);
+    update_feed_counts(siv, &db, feed_ids);
+    let highlighted = highlighted_articles(siv, &db).len() as i64;
+    let tags: HashMap<String, i64> = db.get_tags().unwrap().into_iter().collect();
+    let (highlighted_row, tag_rows) = siv
+        .with_user_data(|user_data: &mut UserData| {
+            let rows = user_data.tree_rows.as_ref();
+            (
+                rows.and_then(|rows| rows.highlighted),
+                rows.map(|rows| rows.tags.clone()).unwrap_or_default(),
+            )
+        })
         .unwrap();
-    greader.mark_article_as_read(item_id).unwrap();
-
-    refresh_selected_article(siv, item_id, db);
-
     siv.call_on_name("tree", |tree: &mut TreeView<TreeEntry>| {
-        let selected_row = tree.row().unwrap();
-        decrease_unread_count(tree, selected_row);
+        if let Some(row) = highlighted_row {
+            set_count(tree, row, highlighted);
+        }
+        for (tag, row) in tag_rows {
+            set_count(tree, row, tags.get(&tag).copied().unwrap_or(0));
+        }
     });
+    refresh_status_info(siv);
+}
 
+/// Updates the counts after a single article was marked read, `delta` -1,
+/// or unread, `delta` 1.
+fn update_article_counts(siv: &mut Cursive, db: &DB, article: &Article, delta: i64) {
+    update_feed_counts(siv, db, std::slice::from_ref(&article.feed_id));
+    let highlighted = siv
+        .with_user_data(|user_data: &mut UserData| is_highlighted(user_data, article))
+        .unwrap();
+    let tags = db.get_article_tags(&article.id).unwrap();
+    let (highlighted_row, tag_rows) = siv
+        .with_user_data(|user_data: &mut UserData| {
+            let rows = user_data.tree_rows.as_ref();
+            let tag_rows: Vec<usize> = tags
+                .iter()
+                .filter_map(|tag| rows.and_then(|rows| rows.tags.get(tag)).copied())
+                .collect();
+            (rows.and_then(|rows| rows.highlighted), tag_rows)
+        })
+        .unwrap();
     siv.call_on_name("tree", |tree: &mut TreeView<TreeEntry>| {
-        let selected_row = tree.row().unwrap();
-        let parent = tree.item_parent(selected_row);
-        if let Some(p) = parent {
-            decrease_unread_count(tree, p);
+        if let Some(row) = highlighted_row.filter(|_| highlighted) {
+            add_count(tree, row, delta);
+        }
+        for row in tag_rows {
+            add_count(tree, row, delta);
         }
     });
+    refresh_status_info(siv);
 }
 
-fn mark_article_as_unread(siv: &mut Cursive, item_id: &str, db: DB) {
+fn toggle_article_read(s: &mut Cursive) {
+    let selected_item = s
+        .call_on_name("content", |view: &mut SelectView<Article>| view.selection())
+        .flatten();
+    let Some(selected_item) = selected_item else {
+        return;
+    };
+    let db = open_db(s);
+    if selected_item.unread() {
+        mark_article_as_read(s, &selected_item.id, db);
+    } else {
+        mark_article_as_unread(s, &selected_item.id, db);
+    }
+
+    content_select_down(s);
+}
+
+/// Marks every unread article in the content panel as read.
+fn mark_all_as_read(siv: &mut Cursive) {
+    let ids = siv
+        .call_on_name("content", |view: &mut SelectView<Article>| {
+            view.iter()
+                .filter(|(_, article)| article.unread())
+                .map(|(_, article)| article.id.clone())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if ids.is_empty() {
+        return;
+    }
     let greader = siv
         .with_user_data(|user_data: &mut UserData| user_data.greader.clone())
         .unwrap();
-    greader.mark_article_as_unread(item_id).unwrap();
-
-    refresh_selected_article(siv, item_id, db);
+    if let Err(error) = greader.mark_articles_as_read(&ids) {
+        set_status(siv, &format!("{:#}", error), Severity::Error);
+        return;
+    }
 
-    siv.call_on_name("tree", |tree: &mut TreeView<TreeEntry>| {
-        let selected_row = tree.row().unwrap();
-        increase_unread_count(tree, selected_row);
-    });
+    let db = open_db(siv);
+    let highlighter = current_highlighter(siv);
+    let feed_ids = siv.call_on_name("content", |view: &mut SelectView<Article>| {
+        let selected = view.selected_id();
+        let articles: Vec<Article> = view
+            .iter()
+            .map(|(_, article)| db.get_article(article.id.clone()).unwrap())
+            .collect();
+        view.clear();
+        for article in articles {
+            view.add_item(article.draw(&highlighter), article);
+        }
+        if let Some(selected) = selected {
+            view.set_selection(selected);
+        }
+        view.iter()
+            .map(|(_, article)| article.feed_id.clone())
+            .collect::<BTreeSet<_>>()
+    });
+    let feed_ids: Vec<String> = feed_ids.into_iter().flatten().collect();
+    update_unread_counts(siv, &feed_ids);
+    set_status(
+        siv,
+        &format!("Marked {} as read", ids.len()),
+        Severity::Info,
+    );
+}
 
-    siv.call_on_name("tree", |tree: &mut TreeView<TreeEntry>| {
-        let selected_row = tree.row().unwrap();
-        let parent = tree.item_parent(selected_row);
-        if let Some(p) = parent {
-            increase_unread_count(tree, p);
+fn export_starred(siv: &mut Cursive) {
+    let settings = siv
+        .with_user_data(|user_data: &mut UserData| user_data.config.export.clone())
+        .unwrap();
+    match export::export_starred(&open_db(siv), &settings) {
+        Ok(paths) => set_status(
+            siv,
+            &format!(
+                "Saved {} articles to {}",
+                paths.len(),
+                settings.dir().display()
+            ),
+            Severity::Info,
+        ),
+        Err(error) => set_status(siv, &format!("Export failed: {}", error), Severity::Error),
+    }
+}
+
+/// Redraws the row of the article after its state changed, wherever it is
+/// in the list, leaving the selection where it is.
+fn refresh_article_row(siv: &mut Cursive, item_id: &str, db: DB) {
+    let highlighter = current_highlighter(siv);
+    let article = db.get_article(item_id.to_string()).unwrap();
+    siv.call_on_name("content", move |view: &mut SelectView<Article>| {
+        if let Some((label, value)) = view.iter_mut().find(|(_, row)| row.id == article.id) {
+            *label = article.draw(&highlighter);
+            *value = article;
         }
     });
 }
 
+fn mark_article_as_read(siv: &mut Cursive, item_id: &str, db: DB) {
+    let greader = siv
+        .with_user_data(|user_data: &mut UserData| user_data.greader.clone())
+        .unwrap();
+    let article = db.get_article(item_id.to_string()).unwrap();
+    if let Err(error) = greader.mark_article_as_read(item_id) {
+        set_status(
+            siv,
+            &format!("Couldn't mark the article as read: {}", error),
+            Severity::Error,
+        );
+        return;
+    }
+    record_read_event(siv, &db, item_id, ReadEvent::Read, None);
+
+    if article.unread() {
+        update_article_counts(siv, &db, &article, -1);
+    }
+    refresh_article_row(siv, item_id, db);
+}
+
+fn mark_article_as_unread(siv: &mut Cursive, item_id: &str, db: DB) {
+    let greader = siv
+        .with_user_data(|user_data: &mut UserData| user_data.greader.clone())
+        .unwrap();
+    let article = db.get_article(item_id.to_string()).unwrap();
+    if let Err(error) = greader.mark_article_as_unread(item_id) {
+        set_status(
+            siv,
+            &format!("Couldn't mark the article as unread: {}", error),
+            Severity::Error,
+        );
+        return;
+    }
+
+    if !article.unread() {
+        update_article_counts(siv, &db, &article, 1);
+    }
+    refresh_article_row(siv, item_id, db);
+}
+
 fn article_details_item(label: &str, value: &str) -> SpannedString<Style> {
     let mut feed = StyledString::styled(
         label,
@@ -380,256 +1242,871 @@ fn article_details_item(label: &str, value: &str) -> SpannedString<Style> {
     feed
 }
 
-#[derive(Clone, Debug)]
-pub struct ArticleDecorator {
-    links: Vec<String>,
+fn show_selected_feed_info(siv: &mut Cursive) {
+    let entry = siv
+        .call_on_name("tree", |tree: &mut TreeView<TreeEntry>| {
+            tree.row().and_then(|row| tree.borrow_item(row).cloned())
+        })
+        .flatten();
+    match entry {
+        Some(entry) if entry.kind == EntryKind::Feed => show_feed_info(siv, &entry.id),
+        _ => set_status(siv, "Select a feed to see its details", Severity::Warning),
+    }
 }
 
-impl ArticleDecorator {
-    #[cfg_attr(feature = "clippy", allow(new_without_default_derive))]
-    pub fn new() -> ArticleDecorator {
-        ArticleDecorator { links: Vec::new() }
+fn show_feed_info(siv: &mut Cursive, feed_id: &str) {
+    let db = open_db(siv);
+    let info = match db.get_feed_info(feed_id) {
+        Ok(info) => info,
+        Err(error) => {
+            set_status(
+                siv,
+                &format!("Couldn't load the feed: {}", error),
+                Severity::Error,
+            );
+            return;
+        }
+    };
+
+    let last_item = info
+        .last_item
+        .map(utils::formatted_pub_date)
+        .unwrap_or_else(|| String::from("never"));
+    let frequency = match info.posting_interval() {
+        Some(seconds) if seconds >= 2 * utils::SECONDS_IN_DAY => {
+            format!("every {} days", seconds / utils::SECONDS_IN_DAY)
+        }
+        Some(seconds) if seconds >= 2 * 60 * 60 => format!("every {} hours", seconds / (60 * 60)),
+        Some(seconds) => format!("every {} minutes", (seconds / 60).max(1)),
+        None => String::from("unknown"),
+    };
+    let mut layout = LinearLayout::vertical();
+    for (label, value) in [
+        ("Site: ", info.link.clone()),
+        ("Feed: ", info.rss_link.clone()),
+        ("Icon: ", info.icon_url.clone().unwrap_or_default()),
+        ("Categories: ", info.categories.clone()),
+        (
+            "Articles: ",
+            format!(
+                "{} ({} unread, {} starred)",
+                info.articles, info.unread, info.starred
+            ),
+        ),
+        ("Last item: ", last_item),
+        ("Posts: ", frequency),
+        (
+            "Last error: ",
+            info.last_error
+                .clone()
+                .unwrap_or_else(|| String::from("none")),
+        ),
+    ] {
+        layout.add_child(TextView::new(article_details_item(label, &value)));
     }
-}
 
-impl TextDecorator for ArticleDecorator {
-    type Annotation = ();
+    let site = info.link.clone();
+    let feed_url = info.rss_link.clone();
+    siv.add_layer(
+        Dialog::around(layout.scrollable())
+            .title(info.title.as_str().truncate_ellipse(60))
+            .button("Open site", move |s| open_link(&site, s))
+            .button("Copy feed URL", move |s| yank(s, &feed_url))
+            .dismiss_button("Close")
+            .max_width(100),
+    );
+}
 
-    fn decorate_link_start(&mut self, url: &str) -> (String, Self::Annotation) {
-        /* self.links.push(url.to_string());
-        ("[".to_string(), ()) */
-        ("".to_string(), ())
+fn content_on_submit(siv: &mut Cursive, item: &Article) {
+    if item.unread() {
+        let db = open_db(siv);
+        mark_article_as_read(siv, &item.id, db);
     }
+    show_article(siv, item);
+}
 
-    fn decorate_link_end(&mut self) -> String {
-        // format!("][{}]", self.links.len())
-        "".to_string()
-    }
+fn show_article(siv: &mut Cursive, item: &Article) {
+    let layout = article_layout(siv, item, true, "article_body");
 
-    fn decorate_em_start(&mut self) -> (String, Self::Annotation) {
-        ("*".to_string(), ())
-    }
+    let mut view = OnEventView::new(
+        LinearLayout::vertical()
+            .child(
+                Dialog::around(layout.scrollable())
+                    .button("Close", close_layer)
+                    .padding(cursive::view::Margins {
+                        left: 0,
+                        right: 0,
+                        top: 0,
+                        bottom: 0,
+                    })
+                    .title(item.title.as_str().truncate_ellipse(70))
+                    .with_name("reader")
+                    .full_screen(),
+            )
+            .child(TextView::new("").with_name("status")),
+    );
 
-    fn decorate_em_end(&mut self) -> String {
-        "*".to_string()
-    }
+    keymap::install(&mut view, Scope::Reader);
 
-    fn decorate_strong_start(&mut self) -> (String, Self::Annotation) {
-        ("**".to_string(), ())
-    }
+    siv.add_fullscreen_layer(view);
+    start_reading(siv, &item.id);
+}
 
-    fn decorate_strong_end(&mut self) -> String {
-        "**".to_string()
+/// The article header, body and links. The full content is only fetched
+/// for feeds set up for it when `fetch` is set, otherwise a cached copy is
+/// used. A fetch shows up in the reader once it's done. The body is named
+/// `body_name`.
+fn article_layout(
+    siv: &mut Cursive,
+    item: &Article,
+    fetch: bool,
+    body_name: &'static str,
+) -> LinearLayout {
+    let db = open_db(siv);
+    let article_details = db.get_article_details(&item.id).unwrap();
+    if fetch && article_details.full_content.is_none() {
+        let feed_settings = siv
+            .with_user_data(|user_data: &mut UserData| {
+                user_data
+                    .config
+                    .feed_settings(&item.feed_id, &article_details.feed_title)
+            })
+            .unwrap();
+        if feed_settings.full_content {
+            fetch_full_content(siv, item);
+        }
     }
+    let content = article_details
+        .full_content
+        .clone()
+        .unwrap_or_else(|| item.content.clone());
 
-    fn decorate_strikeout_start(&mut self) -> (String, Self::Annotation) {
-        ("".to_string(), ())
+    let mut layout = LinearLayout::vertical()
+        .child(TextView::new(article_details_item(
+            "Feed: ",
+            &article_details.feed_title,
+        )))
+        .child(TextView::new(article_details_item(
+            "Title: ",
+            &article_details.title,
+        )))
+        .child(if article_details.author.is_empty() {
+            TextView::new("")
+        } else {
+            TextView::new(article_details_item("Author: ", &article_details.author))
+        })
+        .child(TextView::new(article_details_item(
+            "Date: ",
+            &utils::formatted_pub_date(article_details.pub_date),
+        )))
+        .child(TextView::new(article_details_item(
+            "Link: ",
+            &article_details.link,
+        )));
+    let tags = db.get_article_tags(&item.id).unwrap();
+    if !tags.is_empty() {
+        layout.add_child(TextView::new(article_details_item(
+            "Tags: ",
+            &tags.join(", "),
+        )));
     }
-
-    fn decorate_strikeout_end(&mut self) -> String {
-        "".to_string()
+    let enclosures = db.get_enclosures(&item.id).unwrap();
+    for enclosure in &enclosures {
+        layout.add_child(TextView::new(article_details_item(
+            "Media: ",
+            &enclosure.draw(),
+        )));
     }
+    layout.add_child(DummyView);
 
-    fn decorate_code_start(&mut self) -> (String, Self::Annotation) {
-        ("`".to_string(), ())
+    let base = Url::parse(&article_details.link)
+        .or_else(|_| Url::parse(&article_details.feed_link))
+        .ok();
+    let previews = siv
+        .with_user_data(|user_data: &mut UserData| user_data.config.image_preview.enabled())
+        .unwrap();
+    let body = ArticleBody::with_options(&content, base, previews, current_highlighter(siv));
+    let links = body.links().to_vec();
+    if previews {
+        images::prefetch(body.images().to_vec(), siv.cb_sink().clone(), move |s| {
+            s.call_on_name(body_name, |body: &mut ArticleBody| body.invalidate());
+        });
+    }
+    layout.add_child(body.with_name(body_name));
+    if !links.is_empty() {
+        layout.add_child(DummyView);
     }
+    for (index, link) in (1..).zip(&links) {
+        layout.add_child(TextView::new(article_details_item(
+            &format!("[{}]: ", index),
+            link,
+        )));
+    }
+    layout
+}
+
+/// The article shown in the reader.
+fn reader_article(siv: &mut Cursive) -> Option<Article> {
+    let article_id = siv
+        .with_user_data(|user_data: &mut UserData| {
+            user_data.reading.as_ref().map(|(id, _)| id.clone())
+        })
+        .flatten()?;
+    open_db(siv).get_article(article_id).ok()
+}
+
+/// Position of the article open in the reader in the content panel, which
+/// may have moved on or changed since it was opened.
+fn reader_index(siv: &mut Cursive) -> Option<usize> {
+    let article_id = siv
+        .with_user_data(|user_data: &mut UserData| {
+            user_data.reading.as_ref().map(|(id, _)| id.clone())
+        })
+        .flatten()?;
+    siv.call_on_name("content", |view: &mut SelectView<Article>| {
+        view.iter()
+            .position(|(_, article)| article.id == article_id)
+    })
+    .flatten()
+}
+
+fn reader_links(siv: &mut Cursive) -> Vec<String> {
+    siv.call_on_name("article_body", |body: &mut ArticleBody| {
+        body.links().to_vec()
+    })
+    .unwrap_or_default()
+}
+
+fn reader_next(siv: &mut Cursive) {
+    reader_step(siv, true);
+}
+
+fn reader_previous(siv: &mut Cursive) {
+    reader_step(siv, false);
+}
 
-    fn decorate_code_end(&mut self) -> String {
-        "`".to_string()
+/// Replaces the reader with the adjacent article of the content panel,
+/// selecting it and marking it read like opening it from the list.
+fn reader_step(siv: &mut Cursive, forward: bool) {
+    let current = reader_index(siv);
+    let article = siv
+        .call_on_name("content", |view: &mut SelectView<Article>| {
+            let selected = current.or_else(|| view.selected_id())?;
+            let index = if forward {
+                Some(selected + 1).filter(|index| *index < view.len())
+            } else {
+                selected.checked_sub(1)
+            }?;
+            view.set_selection(index);
+            view.selection()
+        })
+        .flatten();
+    match article {
+        Some(article) => {
+            siv.pop_layer();
+            preview::show_selected(siv);
+            content_on_submit(siv, &article);
+        }
+        None if forward => set_status(siv, "This is the last article", Severity::Info),
+        None => set_status(siv, "This is the first article", Severity::Info),
     }
+}
 
-    fn decorate_preformat_first(&mut self) -> Self::Annotation {
-        ()
+fn reader_open_link(siv: &mut Cursive, number: usize) {
+    if let Some(link) = reader_links(siv).get(number - 1) {
+        open_link(link, siv);
     }
-    fn decorate_preformat_cont(&mut self) -> Self::Annotation {
-        ()
+}
+
+fn reader_open_article(siv: &mut Cursive) {
+    if let Some(article) = reader_article(siv) {
+        open_link(&article.link, siv);
+        let db = open_db(siv);
+        record_read_event(siv, &db, &article.id, ReadEvent::Browser, None);
     }
+}
 
-    fn decorate_image(&mut self, title: &str) -> (String, Self::Annotation) {
-        (format!("[{}]", title), ())
+fn reader_yank(siv: &mut Cursive) {
+    if let Some(article) = reader_article(siv) {
+        yank(siv, &article.link);
     }
+}
 
-    fn header_prefix(&mut self, level: usize) -> String {
-        "#".repeat(level) + " "
+fn reader_play(siv: &mut Cursive) {
+    if let Some(article) = reader_article(siv) {
+        let enclosures = open_db(siv).get_enclosures(&article.id).unwrap();
+        choose_enclosure(siv, &enclosures, "Play", play_enclosure);
     }
+}
 
-    fn quote_prefix(&mut self) -> String {
-        "> ".to_string()
+fn reader_download(siv: &mut Cursive) {
+    if let Some(article) = reader_article(siv) {
+        let enclosures = open_db(siv).get_enclosures(&article.id).unwrap();
+        choose_enclosure(siv, &enclosures, "Download", download_enclosure);
     }
+}
 
-    fn unordered_item_prefix(&mut self) -> String {
-        "* ".to_string()
+fn reader_save(siv: &mut Cursive) {
+    if let Some(article) = reader_article(siv) {
+        save_article(siv, &article.id);
     }
+}
 
-    fn ordered_item_prefix(&mut self, i: i64) -> String {
-        format!("{}. ", i)
+fn reader_share(siv: &mut Cursive) {
+    if let Some(article) = reader_article(siv) {
+        share_article(siv, &article.id);
     }
+}
 
-    fn finalise(self) -> Vec<TaggedLine<()>> {
-        Vec::new()
-        /* self.links
-        .into_iter()
-        .enumerate()
-        .map(|(idx, s)| TaggedLine::from_string(format!("[{}]: {}", idx + 1, s), &()))
-        .collect() */
+fn reader_edit_tags(siv: &mut Cursive) {
+    if let Some(article) = reader_article(siv) {
+        edit_tags(siv, &article);
     }
+}
 
-    fn make_subblock_decorator(&self) -> Self {
-        ArticleDecorator::new()
+fn reader_fetch_full_content(siv: &mut Cursive) {
+    if let Some(article) = reader_article(siv) {
+        fetch_full_content(siv, &article);
     }
 }
 
-fn link_from_tag(tag: &Vec<RichAnnotation>) -> Option<String> {
-    let mut link = None;
-    for annotation in tag {
-        if let RichAnnotation::Link(ref text) = *annotation {
-            link = Some(text.clone());
-        }
+fn reader_list_links(siv: &mut Cursive) {
+    let Some(article) = reader_article(siv) else {
+        return;
+    };
+    let mut select = SelectView::new();
+    select.add_item(article.link.clone(), article.link.clone());
+    for (index, link) in (1..).zip(reader_links(siv)) {
+        select.add_item(format!("[{}] {}", index, link), link);
     }
-    link
+
+    select.set_on_submit(|siv: &mut Cursive, item: &String| {
+        open_link(item, siv);
+    });
+
+    let select = OnEventView::new(select)
+        .on_pre_event_inner('k', |s, _| {
+            let cb = s.select_up(1);
+            Some(cursive::event::EventResult::Consumed(Some(cb)))
+        })
+        .on_pre_event_inner('j', |s, _| {
+            let cb = s.select_down(1);
+            Some(cursive::event::EventResult::Consumed(Some(cb)))
+        })
+        .on_pre_event_inner('y', |s, _| {
+            let link = s.selection()?.to_string();
+            Some(cursive::event::EventResult::with_cb(move |s| {
+                yank(s, &link)
+            }))
+        });
+
+    let panel = Panel::new(select).full_height().full_width().scrollable();
+    siv.add_fullscreen_layer(panel)
 }
 
-fn find_links(lines: &Vec<TaggedLine<Vec<RichAnnotation>>>) -> Vec<String> {
-    let mut map = Vec::new();
-    for line in lines {
-        for ts in line.tagged_strings() {
-            let link = link_from_tag(&ts.tag);
-            if let Some(l) = link {
-                if !map.contains(&l) {
-                    map.push(l);
-                }
-            }
-        }
+/// Starts timing the article in the reader, keeping the running time when
+/// the same article is shown again, e.g. after fetching its full content.
+fn start_reading(siv: &mut Cursive, article_id: &str) {
+    let same = siv
+        .with_user_data(|user_data: &mut UserData| {
+            user_data
+                .reading
+                .as_ref()
+                .is_some_and(|(id, _)| id == article_id)
+        })
+        .unwrap();
+    if !same {
+        finish_reading(siv);
+        siv.with_user_data(|user_data: &mut UserData| {
+            user_data.reading = Some((article_id.to_string(), Instant::now()));
+        });
     }
-    map
 }
 
-fn content_on_submit(siv: &mut Cursive, item: &Article) {
-    if item.unread() {
-        let db = DB::new();
-        mark_article_as_read(siv, &item.id, db);
+/// Stats are only bookkeeping, so failing to store them, e.g. while another
+/// process holds the database, is reported and otherwise ignored.
+fn record_read_event(
+    siv: &mut Cursive,
+    db: &DB,
+    article_id: &str,
+    kind: ReadEvent,
+    duration: Option<i64>,
+) {
+    if let Err(error) = db.record_read_event(article_id, kind, duration) {
+        set_status(
+            siv,
+            &format!("Couldn't record reading stats: {:#}", error),
+            Severity::Warning,
+        );
     }
-    let db = DB::new();
-    let article_details = db.get_article_details(&item.id).unwrap();
-    let mut layout = LinearLayout::vertical()
-        .child(TextView::new(article_details_item(
-            "Feed: ",
-            &article_details.feed_title,
-        )))
-        .child(TextView::new(article_details_item(
-            "Title: ",
-            &article_details.title,
-        )))
-        .child(if article_details.author.is_empty() {
-            TextView::new("")
-        } else {
-            TextView::new(article_details_item("Author: ", &article_details.author))
-        })
-        .child(TextView::new(article_details_item(
-            "Date: ",
-            &utils::formatted_pub_date(article_details.pub_date),
-        )))
-        .child(TextView::new(article_details_item(
-            "Link: ",
-            &article_details.link,
-        )))
-        .child(DummyView)
-        .child(TextView::new(html2text::from_read_with_decorator(
-            &item.content.as_bytes()[..],
-            80,
-            ArticleDecorator::new(),
-        )));
-    let finder = LinkFinder::new();
-    let links: Vec<_> = finder.links(&item.content).collect();
-    if links.len() > 0 {
-        layout.add_child(DummyView);
+}
+
+fn finish_reading(siv: &mut Cursive) {
+    let reading = siv
+        .with_user_data(|user_data: &mut UserData| user_data.reading.take())
+        .flatten();
+    if let Some((article_id, started)) = reading {
+        let seconds = started.elapsed().as_secs() as i64;
+        if seconds > 0 {
+            let db = open_db(siv);
+            record_read_event(siv, &db, &article_id, ReadEvent::Reader, Some(seconds));
+        }
     }
-    let mut index = 1;
-    let mut links_cache = vec![];
-    links_cache.push(article_details.link.clone());
+}
 
-    for link in links {
-        layout.add_child(TextView::new(article_details_item(
-            &format!("[{}]: ", index),
-            link.as_str(),
-        )));
-        links_cache.push(link.as_str().to_string());
-        index += 1;
+/// Pops the top layer and stops the reading timer once the reader is gone.
+fn close_layer(siv: &mut Cursive) {
+    siv.pop_layer();
+    if siv
+        .call_on_name("article_body", |_: &mut ArticleBody| ())
+        .is_none()
+    {
+        finish_reading(siv);
     }
+}
 
-    let mut view = OnEventView::new(
-        Dialog::around(layout.scrollable())
-            .button("Close", |s| {
-                s.pop_layer();
-            })
-            .padding(cursive::view::Margins {
-                left: 0,
-                right: 0,
-                top: 0,
-                bottom: 0,
-            })
-            .title(item.title.as_str().truncate_ellipse(70))
+fn show_stats(siv: &mut Cursive) {
+    let report = match Stats::collect(&open_db(siv), stats::DEAD_AFTER_MONTHS) {
+        Ok(stats) => stats.report(),
+        Err(error) => {
+            set_status(
+                siv,
+                &format!("Couldn't read the stats: {}", error),
+                Severity::Error,
+            );
+            return;
+        }
+    };
+    siv.add_fullscreen_layer(
+        Dialog::around(TextView::new(report).scrollable())
+            .title("Reading stats")
+            .button("Close", close_layer)
             .full_screen(),
     );
+}
 
-    view.set_on_event('o', move |s| open_link(&article_details.link, s));
+/// Lets the user edit the tags of the article as a comma separated list.
+fn edit_tags(siv: &mut Cursive, article: &Article) {
+    let tags = open_db(siv).get_article_tags(&article.id).unwrap();
+    let article = article.clone();
+    let editor = EditView::new()
+        .content(tags.join(", "))
+        .on_submit(move |s, text| {
+            s.pop_layer();
+            save_tags(s, &article, &tags, text);
+        })
+        .min_width(40);
+    siv.add_layer(
+        Dialog::around(editor)
+            .title("Tags, separated by commas")
+            .dismiss_button("Cancel"),
+    );
+}
 
-    view.set_on_event('u', move |s: &mut Cursive| {
-        let mut select = SelectView::new();
-        for link in &links_cache {
-            select.add_item_str(link);
+fn save_tags(siv: &mut Cursive, article: &Article, old_tags: &[String], text: &str) {
+    let mut new_tags: Vec<String> = Vec::new();
+    for tag in text.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+        if !new_tags.iter().any(|t| t == tag) {
+            new_tags.push(tag.to_string());
         }
-
-        select.set_on_submit(|siv: &mut Cursive, item: &String| {
-            open_link(&item, siv);
+    }
+    let greader = siv
+        .with_user_data(|user_data: &mut UserData| user_data.greader.clone())
+        .unwrap();
+    let result = new_tags
+        .iter()
+        .filter(|tag| !old_tags.contains(tag))
+        .try_for_each(|tag| greader.add_article_tag(&article.id, tag))
+        .and_then(|_| {
+            old_tags
+                .iter()
+                .filter(|tag| !new_tags.contains(tag))
+                .try_for_each(|tag| greader.remove_article_tag(&article.id, tag))
         });
+    if let Err(error) = result {
+        set_status(siv, &format!("{:#}", error), Severity::Error);
+        return;
+    }
 
-        let select = OnEventView::new(select)
-            .on_pre_event_inner('k', |s, _| {
-                let cb = s.select_up(1);
-                Some(cursive::event::EventResult::Consumed(Some(cb)))
-            })
-            .on_pre_event_inner('j', |s, _| {
-                let cb = s.select_down(1);
-                Some(cursive::event::EventResult::Consumed(Some(cb)))
+    // Show the new tags in the article and the tree
+    siv.pop_layer();
+    show_article(siv, article);
+    rebuild_tree(siv);
+}
+
+/// Runs `action` on the only enclosure, or lets the user pick one.
+fn choose_enclosure(
+    siv: &mut Cursive,
+    enclosures: &[Enclosure],
+    title: &str,
+    action: fn(&mut Cursive, &Enclosure),
+) {
+    match enclosures {
+        [] => {}
+        [enclosure] => action(siv, enclosure),
+        _ => {
+            let mut select = SelectView::new();
+            for enclosure in enclosures {
+                select.add_item(enclosure.draw(), enclosure.clone());
+            }
+            select.set_on_submit(move |s: &mut Cursive, enclosure: &Enclosure| {
+                s.pop_layer();
+                action(s, enclosure);
             });
+            siv.add_layer(
+                Dialog::around(select.scrollable())
+                    .title(title)
+                    .dismiss_button("Cancel"),
+            );
+        }
+    }
+}
 
-        let panel = Panel::new(select).full_height().full_width().scrollable();
-        s.add_fullscreen_layer(panel)
+fn play_enclosure(siv: &mut Cursive, enclosure: &Enclosure) {
+    let player = siv
+        .with_user_data(|user_data: &mut UserData| user_data.config.player.clone())
+        .unwrap()
+        .unwrap_or_else(|| "mpv".to_string());
+    let mut parts = player.split_whitespace();
+    let program = parts.next().unwrap_or("mpv");
+    let result = std::process::Command::new(program)
+        .args(parts)
+        .arg(&enclosure.url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    if let Err(error) = result {
+        siv.add_layer(Dialog::info(format!(
+            "Couldn't start {}:\n{:#}",
+            program, error
+        )));
+    }
+}
+
+fn download_enclosure(siv: &mut Cursive, enclosure: &Enclosure) {
+    siv.with_user_data(|user_data: &mut UserData| {
+        user_data
+            .downloads
+            .push(enclosure.clone(), user_data.config.download_dir());
     });
+    refresh_status_info(siv);
+}
 
-    siv.add_fullscreen_layer(view)
+/// Downloads the article page on a background thread, caches its main
+/// content and shows it if the article is still open in the reader.
+fn fetch_full_content(siv: &mut Cursive, article: &Article) {
+    set_status(siv, "Fetching the full article", Severity::Info);
+    let article = article.clone();
+    let cb_sink = siv.cb_sink().clone();
+    std::thread::spawn(move || {
+        let content = extract_full_content(&article.link);
+        cb_sink
+            .send(Box::new(move |s: &mut Cursive| {
+                store_full_content(s, &article, content)
+            }))
+            .ok();
+    });
+}
+
+fn extract_full_content(link: &str) -> Result<String, String> {
+    let output = utils::fetch_page(link.to_string())
+        .map_err(|error| format!("Couldn't fetch {}: {:#}", link, error))?;
+    let html = String::from_utf8_lossy(&output.stdout);
+    readability::extract(&html)
+        .ok_or_else(|| format!("Couldn't find the article content in {}", link))
+}
+
+fn store_full_content(siv: &mut Cursive, article: &Article, content: Result<String, String>) {
+    let stored = content.and_then(|content| {
+        open_db(siv)
+            .set_article_full_content(&article.id, &content)
+            .map_err(|error| format!("{:#}", error))
+    });
+    if let Err(error) = stored {
+        set_status(siv, &error, Severity::Error);
+        return;
+    }
+    if reader_article(siv).is_some_and(|open| open.id == article.id) {
+        let layout = article_layout(siv, article, false, "article_body");
+        siv.call_on_name("reader", |dialog: &mut Dialog| {
+            dialog.set_content(layout.scrollable());
+        });
+    }
+    set_status(siv, "Fetched the full article", Severity::Info);
 }
 
 fn open_article(s: &mut Cursive) {
     let selected_item = s
-        .call_on_name("content", move |view: &mut SelectView<Article>| {
-            view.selection().unwrap()
-        })
-        .unwrap();
+        .call_on_name("content", |view: &mut SelectView<Article>| view.selection())
+        .flatten();
+    let Some(selected_item) = selected_item else {
+        return;
+    };
 
     open_link(&selected_item.link, s);
+    let db = open_db(s);
+    record_read_event(s, &db, &selected_item.id, ReadEvent::Browser, None);
 
     if selected_item.unread() {
-        let db = DB::new();
+        let db = open_db(s);
         mark_article_as_read(s, &selected_item.id, db);
     }
 
     content_select_down(s);
 }
 
+fn save_selected_article(s: &mut Cursive) {
+    let selected_item = s
+        .call_on_name("content", |view: &mut SelectView<Article>| view.selection())
+        .unwrap();
+    if let Some(item) = selected_item {
+        save_article(s, &item.id);
+    }
+}
+
+fn save_article(siv: &mut Cursive, article_id: &str) {
+    let settings = siv
+        .with_user_data(|user_data: &mut UserData| user_data.config.export.clone())
+        .unwrap();
+    let db = open_db(siv);
+    let message = match export::save_article(&db, &settings, article_id) {
+        Ok(path) => format!("Saved to {}", path.display()),
+        Err(error) => format!("Something went wrong while saving:\n{:#}", error),
+    };
+    siv.add_layer(Dialog::info(message));
+}
+
+fn share_selected_article(s: &mut Cursive) {
+    let selected_item = s
+        .call_on_name("content", |view: &mut SelectView<Article>| view.selection())
+        .unwrap();
+    if let Some(item) = selected_item {
+        share_article(s, &item.id);
+    }
+}
+
+/// Sends the article to the only configured share target, or lets the user
+/// pick one.
+fn share_article(siv: &mut Cursive, article_id: &str) {
+    let targets = siv
+        .with_user_data(|user_data: &mut UserData| user_data.config.share.clone())
+        .unwrap();
+    let details = match open_db(siv).get_article_details(article_id) {
+        Ok(details) => details,
+        Err(error) => {
+            set_status(siv, &format!("{:#}", error), Severity::Error);
+            return;
+        }
+    };
+    let article = SharedArticle {
+        id: article_id.to_string(),
+        title: details.title,
+        link: details.link,
+        feed: details.feed_title,
+        author: details.author,
+        pub_date: details.pub_date,
+    };
+
+    if targets.len() < 2 {
+        match targets.into_iter().next() {
+            Some((name, target)) => send_to(siv, name, target, article),
+            None => set_status(
+                siv,
+                "No [share.<name>] targets in config.toml",
+                Severity::Warning,
+            ),
+        }
+        return;
+    }
+
+    let mut select = SelectView::new();
+    for (name, target) in targets {
+        select.add_item(name.clone(), (name, target));
+    }
+    select.set_on_submit(
+        move |s: &mut Cursive, (name, target): &(String, ShareTarget)| {
+            s.pop_layer();
+            send_to(s, name.clone(), target.clone(), article.clone());
+        },
+    );
+    siv.add_layer(
+        Dialog::around(select.scrollable())
+            .title("Send to")
+            .dismiss_button("Cancel"),
+    );
+}
+
+fn send_to(siv: &mut Cursive, name: String, target: ShareTarget, article: SharedArticle) {
+    siv.with_user_data(|user_data: &mut UserData| user_data.pending_shares += 1);
+    set_status(siv, &format!("Sending to {}...", name), Severity::Info);
+    let cb_sink = siv.cb_sink().clone();
+    std::thread::spawn(move || {
+        let result = share::send(&target, &article);
+        cb_sink
+            .send(Box::new(move |s: &mut Cursive| {
+                s.with_user_data(|user_data: &mut UserData| user_data.pending_shares -= 1);
+                match result {
+                    Ok(()) => set_status(s, &format!("Sent to {}", name), Severity::Info),
+                    Err(error) => set_status(
+                        s,
+                        &format!("Sending to {} failed: {}", name, error),
+                        Severity::Error,
+                    ),
+                }
+            }))
+            .ok();
+    });
+}
+
+fn yank_selected_article(s: &mut Cursive) {
+    let selected_item = s
+        .call_on_name("content", |view: &mut SelectView<Article>| view.selection())
+        .unwrap();
+    if let Some(item) = selected_item {
+        yank(s, &item.link);
+    }
+}
+
+fn yank(siv: &mut Cursive, text: &str) {
+    let command = siv
+        .with_user_data(|user_data: &mut UserData| user_data.config.clipboard_command.clone())
+        .unwrap();
+    match clipboard::copy(text, command.as_deref()) {
+        Ok(()) => set_status(siv, &format!("Copied {}", text), Severity::Info),
+        Err(error) => set_status(siv, &error, Severity::Error),
+    }
+}
+
+/// Shows a transient message in the status bar of every layer.
+fn set_status(siv: &mut Cursive, message: &str, severity: Severity) {
+    let text = match severity {
+        Severity::Info => StyledString::plain(message),
+        Severity::Warning => StyledString::styled(message, Color::Dark(BaseColor::Yellow)),
+        Severity::Error => StyledString::styled(message, Color::Dark(BaseColor::Red)),
+    };
+    siv.call_on_all_named("status", |view: &mut TextView| {
+        view.set_content(text.clone());
+    });
+    refresh_status_info(siv);
+
+    let generation = siv
+        .with_user_data(|user_data: &mut UserData| {
+            user_data.status_generation += 1;
+            user_data.status_generation
+        })
+        .unwrap();
+    let cb_sink = siv.cb_sink().clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(STATUS_TIMEOUT);
+        cb_sink
+            .send(Box::new(move |s: &mut Cursive| {
+                let current = s
+                    .with_user_data(|user_data: &mut UserData| user_data.status_generation)
+                    .unwrap();
+                if current == generation {
+                    s.call_on_all_named("status", |view: &mut TextView| view.set_content(""));
+                }
+            }))
+            .ok();
+    });
+}
+
+/// Updates the right side of the status bar: sort order, background work
+/// still running, unread articles and the last sync.
+fn refresh_status_info(siv: &mut Cursive) {
+    let db_path = siv
+        .with_user_data(|user_data: &mut UserData| user_data.profile.db_path.clone())
+        .unwrap();
+    let (filter, sort, downloads, shares, db) = siv
+        .with_user_data(|user_data: &mut UserData| {
+            let db = user_data
+                .status_db
+                .get_or_insert_with(|| Rc::new(DB::new(&db_path)));
+            (
+                user_data.filter.clone(),
+                user_data.sort,
+                user_data.downloads.pending(),
+                user_data.pending_shares,
+                Rc::clone(db),
+            )
+        })
+        .unwrap();
+    let mut info = Vec::new();
+    if let Some(filter) = filter {
+        info.push(filter);
+    }
+    info.push(format!("sorted {}", sort.label()));
+    if downloads > 0 {
+        info.push(format!("{} downloads pending", downloads));
+    }
+    if shares > 0 {
+        info.push(format!("{} shares pending", shares));
+    }
+    if let Ok(unread) = db.get_total_unread_count() {
+        info.push(format!("{} unread", unread));
+    }
+    info.push(match Greader::last_synced(&db) {
+        Some(date) => format!("synced {}", utils::formatted_pub_date(date)),
+        None => String::from("never synced"),
+    });
+    let info = format!(" {} ", info.join(" | "));
+    siv.call_on_name("status_info", |view: &mut TextView| view.set_content(info));
+}
+
 fn open_link(link: &str, siv: &mut Cursive) {
-    let browser = siv
-        .with_user_data(|user_data: &mut UserData| user_data.browser.clone())
+    let (browser, terminal_browser) = siv
+        .with_user_data(|user_data: &mut UserData| {
+            (user_data.browser.clone(), user_data.config.terminal_browser)
+        })
         .unwrap();
     let browser = browser.unwrap_or(if utils::is_macos() {
         "open".to_string()
     } else {
         "xdg-open".to_string()
     });
+    let command = browser_command(&browser, link);
+    if command.is_empty() {
+        set_status(siv, "The browser command is empty", Severity::Error);
+        return;
+    }
 
-    std::process::Command::new(browser)
-        .arg(link)
-        .output()
-        .unwrap();
+    if terminal_browser {
+        siv.with_user_data(|user_data: &mut UserData| {
+            user_data.foreground_command = Some(command);
+        });
+        siv.quit();
+        return;
+    }
+
+    let child = std::process::Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    match child {
+        // Reap the browser when it exits, e.g. `xdg-open` right away
+        Ok(mut child) => {
+            std::thread::spawn(move || child.wait());
+        }
+        Err(error) => set_status(
+            siv,
+            &format!("Couldn't run `{}`: {}", command[0], error),
+            Severity::Error,
+        ),
+    }
+}
+
+/// Splits the `browser` setting into arguments, substituting `%u` with the
+/// link or appending it when there is no placeholder.
+fn browser_command(browser: &str, link: &str) -> Vec<String> {
+    let mut command: Vec<String> = browser
+        .split_whitespace()
+        .map(|part| part.replace("%u", link))
+        .collect();
+    if !browser.contains("%u") {
+        command.push(link.to_string());
+    }
+    command
 }
@@ -0,0 +1,323 @@
+//! The keybinding registry. Keys are installed from `BINDINGS`, so the help
+//! overlay and the command palette always match what the keys do.
+
+use super::*;
+use cursive::event::Key;
+use cursive::view::View;
+use std::fmt;
+
+/// Where a binding applies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Scope {
+    Global,
+    Tree,
+    Content,
+    Reader,
+}
+
+impl Scope {
+    fn title(self) -> &'static str {
+        match self {
+            Scope::Global => "Everywhere",
+            Scope::Tree => "Feed list",
+            Scope::Content => "Article list",
+            Scope::Reader => "Article view",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Shortcut {
+    Char(char),
+    Ctrl(char),
+    /// 1 to 9, passed to a `Numbered` action
+    Digits,
+}
+
+impl Shortcut {
+    fn events(self) -> Vec<(Event, usize)> {
+        match self {
+            Shortcut::Char(c) => vec![(Event::Char(c), 0)],
+            Shortcut::Ctrl(c) => vec![(Event::CtrlChar(c), 0)],
+            Shortcut::Digits => (1..=9)
+                .map(|number| {
+                    (
+                        Event::Char(char::from_digit(number, 10).unwrap()),
+                        number as usize,
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for Shortcut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Shortcut::Char(c) => write!(f, "{}", c),
+            Shortcut::Ctrl(c) => write!(f, "Ctrl-{}", c.to_ascii_uppercase()),
+            Shortcut::Digits => write!(f, "1-9"),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Action {
+    Run(fn(&mut Cursive)),
+    Numbered(fn(&mut Cursive, usize)),
+}
+
+struct Binding {
+    scope: Scope,
+    /// Palette-only actions have no key
+    key: Option<Shortcut>,
+    /// Name in the command palette
+    name: &'static str,
+    description: &'static str,
+    action: Action,
+}
+
+const fn bind(
+    scope: Scope,
+    key: Option<Shortcut>,
+    name: &'static str,
+    description: &'static str,
+    action: Action,
+) -> Binding {
+    Binding {
+        scope,
+        key,
+        name,
+        description,
+        action,
+    }
+}
+
+use Action::{Numbered, Run};
+use Scope::{Content, Global, Reader, Tree};
+use Shortcut::{Char, Ctrl, Digits};
+
+#[rustfmt::skip]
+const BINDINGS: &[Binding] = &[
+    bind(Global, Some(Char('?')), "help", "Show this help", Run(show_help)),
+    bind(Global, Some(Char(':')), "command palette", "Run a command by name", Run(show_palette)),
+    bind(Global, Some(Char('h')), "back", "Close the top view", Run(go_back)),
+    bind(Global, Some(Char('R')), "sync", "Sync with the server", Run(sync)),
+    bind(Global, Some(Ctrl('r')), "full resync", "Drop the local cache and sync everything", Run(confirm_full_resync)),
+    bind(Global, Some(Char('P')), "switch profile", "Switch to another profile", Run(select_profile)),
+    bind(Global, Some(Char('A')), "apply rules", "Apply the filter rules to all articles", Run(apply_rules)),
+    bind(Global, Some(Char('I')), "reading stats", "Show reading stats", Run(show_stats)),
+    bind(Global, Some(Char(']')), "next unread article", "Go to the next unread article", Run(next_unread_article)),
+    bind(Global, Some(Char('}')), "next unread feed", "Go to the next feed with unread articles", Run(next_unread_feed_action)),
+    bind(Global, Some(Char('/')), "search", "Search all articles", Run(show_search)),
+    bind(Global, Some(Char('v')), "toggle view mode", "Switch between the stacked and the panes layout", Run(preview::toggle_panes)),
+    bind(Global, None, "export starred", "Save all starred articles", Run(export_starred)),
+    bind(Tree, Some(Char('j')), "next feed", "Move down", Run(tree_focus_down)),
+    bind(Tree, Some(Char('k')), "previous feed", "Move up", Run(tree_focus_up)),
+    bind(Tree, Some(Char('i')), "feed info", "Show details of the feed", Run(show_selected_feed_info)),
+    bind(Content, Some(Char('j')), "next article", "Move down", Run(content_select_down)),
+    bind(Content, Some(Char('k')), "previous article", "Move up", Run(content_select_up)),
+    bind(Content, Some(Char('s')), "sort oldest first", "Sort oldest first", Run(sort_asc)),
+    bind(Content, Some(Char('S')), "sort newest first", "Sort newest first", Run(sort_desc)),
+    bind(Content, Some(Char('o')), "open in browser", "Open in the browser and mark read", Run(open_article)),
+    bind(Content, Some(Char('N')), "toggle read", "Toggle read", Run(toggle_article_read)),
+    bind(Content, Some(Char('M')), "mark all read", "Mark all listed articles read", Run(mark_all_as_read)),
+    bind(Content, Some(Char('w')), "save article", "Save to the export directory", Run(save_selected_article)),
+    bind(Content, Some(Char('b')), "share article", "Send to a share target", Run(share_selected_article)),
+    bind(Content, Some(Char('y')), "copy link", "Copy the link", Run(yank_selected_article)),
+    bind(Reader, Some(Char('n')), "next article", "Show the next article in the list", Run(reader_next)),
+    bind(Reader, Some(Char('p')), "previous article", "Show the previous article in the list", Run(reader_previous)),
+    bind(Reader, Some(Digits), "open link", "Open the numbered link", Numbered(reader_open_link)),
+    bind(Reader, Some(Char('o')), "open in browser", "Open in the browser", Run(reader_open_article)),
+    bind(Reader, Some(Char('y')), "copy link", "Copy the link", Run(reader_yank)),
+    bind(Reader, Some(Char('u')), "list links", "List all links", Run(reader_list_links)),
+    bind(Reader, Some(Char('m')), "play media", "Play an attachment", Run(reader_play)),
+    bind(Reader, Some(Char('D')), "download media", "Download an attachment", Run(reader_download)),
+    bind(Reader, Some(Char('w')), "save article", "Save to the export directory", Run(reader_save)),
+    bind(Reader, Some(Char('b')), "share article", "Send to a share target", Run(reader_share)),
+    bind(Reader, Some(Char('t')), "edit tags", "Edit the tags", Run(reader_edit_tags)),
+    bind(Reader, Some(Char('F')), "fetch full content", "Fetch the full article", Run(reader_fetch_full_content)),
+];
+
+fn scope_bindings(scope: Scope) -> impl Iterator<Item = (Event, Action, usize)> {
+    BINDINGS
+        .iter()
+        .filter(move |binding| binding.scope == scope)
+        .filter_map(|binding| binding.key.map(|key| (key, binding.action)))
+        .flat_map(|(key, action)| {
+            key.events()
+                .into_iter()
+                .map(move |(event, number)| (event, action, number))
+        })
+}
+
+fn run(siv: &mut Cursive, action: Action, number: usize) {
+    match action {
+        Run(action) => action(siv),
+        Numbered(action) => action(siv, number),
+    }
+}
+
+pub(super) fn install_global(siv: &mut Cursive) {
+    for (event, action, number) in scope_bindings(Global) {
+        siv.set_global_callback(event, move |s| run(s, action, number));
+    }
+}
+
+pub(super) fn install<V: View>(view: &mut OnEventView<V>, scope: Scope) {
+    for (event, action, number) in scope_bindings(scope) {
+        view.set_on_event(event, move |s| run(s, action, number));
+    }
+}
+
+fn go_back(siv: &mut Cursive) {
+    if siv.screen().len() > 1 {
+        close_layer(siv);
+    }
+}
+
+fn show_help(siv: &mut Cursive) {
+    let mut help = StyledString::new();
+    for scope in [Global, Tree, Content, Reader] {
+        if !help.is_empty() {
+            help.append_plain("\n");
+        }
+        help.append_styled(
+            format!("{}\n", scope.title()),
+            Style::from(Color::Light(BaseColor::Cyan)).combine(Effect::Bold),
+        );
+        for binding in BINDINGS.iter().filter(|binding| binding.scope == scope) {
+            let key = binding.key.map_or(String::from(":"), |key| key.to_string());
+            help.append_styled(
+                format!("  {:<8}", key),
+                Style::from(Color::Dark(BaseColor::Blue)).combine(Effect::Bold),
+            );
+            help.append_plain(format!("{}\n", binding.description));
+        }
+    }
+    help.append_plain("\nEnter opens the selected feed or article. Commands without a key\nrun from the : palette.");
+    siv.add_layer(
+        Dialog::around(TextView::new(help).scrollable())
+            .title("Keys")
+            .button("Close", close_layer),
+    );
+}
+
+/// Commands the palette can run right now, best match first.
+fn palette_matches(siv: &mut Cursive, query: &str) -> Vec<usize> {
+    let reader_open = siv
+        .call_on_name("article_body", |_: &mut ArticleBody| ())
+        .is_some();
+    // Content commands work on the selected article, which an empty list
+    // doesn't have
+    let has_articles = siv
+        .call_on_name("content", |view: &mut SelectView<Article>| !view.is_empty())
+        .unwrap_or(false);
+    let mut matches: Vec<(i64, usize)> = BINDINGS
+        .iter()
+        .enumerate()
+        .filter(|(_, binding)| matches!(binding.action, Run(_)))
+        .filter(|(_, binding)| binding.scope != Reader || reader_open)
+        .filter(|(_, binding)| binding.scope != Content || has_articles)
+        .filter_map(|(index, binding)| fuzzy_score(query, binding.name).map(|score| (score, index)))
+        .collect();
+    matches.sort_by_key(|(score, index)| (-score, *index));
+    matches.into_iter().map(|(_, index)| index).collect()
+}
+
+fn palette_label(binding: &Binding) -> StyledString {
+    let mut label = StyledString::plain(binding.name);
+    let context = match binding.scope {
+        Global => String::new(),
+        scope => format!(" [{}]", scope.title().to_lowercase()),
+    };
+    let key = binding
+        .key
+        .map_or(String::new(), |key| format!(" ({})", key));
+    label.append_styled(
+        format!("{}{}", context, key),
+        Color::Light(BaseColor::Black),
+    );
+    label
+}
+
+fn fill_palette(siv: &mut Cursive, query: &str) {
+    let matches = palette_matches(siv, query);
+    siv.call_on_name("palette_commands", |select: &mut SelectView<usize>| {
+        select.clear();
+        for index in matches {
+            select.add_item(palette_label(&BINDINGS[index]), index);
+        }
+    });
+}
+
+fn run_palette_command(siv: &mut Cursive, index: usize) {
+    siv.pop_layer();
+    if let Run(action) = BINDINGS[index].action {
+        action(siv);
+    }
+}
+
+fn show_palette(siv: &mut Cursive) {
+    let query = EditView::new()
+        .on_edit(|s, text, _| fill_palette(s, text))
+        .on_submit(|s, _| {
+            let selected = s
+                .call_on_name("palette_commands", |select: &mut SelectView<usize>| {
+                    select.selection()
+                })
+                .flatten();
+            if let Some(index) = selected {
+                run_palette_command(s, *index);
+            }
+        });
+    let query = OnEventView::new(query)
+        .on_pre_event(Event::Key(Key::Down), |s| {
+            s.call_on_name("palette_commands", |select: &mut SelectView<usize>| {
+                select.select_down(1)
+            });
+        })
+        .on_pre_event(Event::Key(Key::Up), |s| {
+            s.call_on_name("palette_commands", |select: &mut SelectView<usize>| {
+                select.select_up(1)
+            });
+        });
+    let mut commands = SelectView::<usize>::new();
+    commands.set_on_submit(|s, index: &usize| run_palette_command(s, *index));
+
+    siv.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(query)
+                .child(DummyView)
+                .child(commands.with_name("palette_commands").scrollable()),
+        )
+        .title("Command")
+        .dismiss_button("Cancel")
+        .min_width(50)
+        .max_height(20),
+    );
+    fill_palette(siv, "");
+}
+
+/// Scores `text` when all characters of `query` appear in it in order,
+/// preferring consecutive characters and word starts.
+fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for c in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = position + text[position..].iter().position(|t| *t == c)?;
+        score += 1;
+        if previous == Some(found.wrapping_sub(1)) {
+            score += 5;
+        }
+        if found == 0 || text[found - 1] == ' ' {
+            score += 3;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
@@ -0,0 +1,161 @@
+//! The preview pane of the `panes` layout, showing the selected article
+//! next to the list.
+
+use super::*;
+use cursive::view::{Selector, View, ViewWrapper};
+use cursive::views::{HideableView, ResizedView};
+use cursive::Vec2;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+type PreviewPane = HideableView<ResizedView<NamedView<Dialog>>>;
+
+/// The pane, hidden until the screen is known to be wide enough.
+pub(super) fn pane() -> NamedView<PreviewPane> {
+    HideableView::new(
+        Dialog::new()
+            .title("Preview")
+            .padding(cursive::view::Margins {
+                left: 0,
+                right: 0,
+                top: 0,
+                bottom: 0,
+            })
+            .with_name("preview")
+            .full_screen(),
+    )
+    .hidden()
+    .with_name("preview_pane")
+}
+
+/// Shows the preview pane in the panes layout while the screen is at least
+/// `min_width` wide, otherwise the list gets the room with articles opening
+/// full screen.
+pub(super) struct Responsive<V> {
+    view: V,
+    min_width: usize,
+    /// Panes layout, switched by `toggle_panes`
+    panes: Arc<AtomicBool>,
+    /// Fills the pane once it's shown again
+    cb_sink: cursive::CbSink,
+}
+
+impl<V> Responsive<V> {
+    pub(super) fn new(
+        view: V,
+        min_width: usize,
+        panes: Arc<AtomicBool>,
+        cb_sink: cursive::CbSink,
+    ) -> Self {
+        Self {
+            view,
+            min_width,
+            panes,
+            cb_sink,
+        }
+    }
+}
+
+impl<V: View> ViewWrapper for Responsive<V> {
+    cursive::wrap_impl!(self.view: V);
+
+    fn wrap_layout(&mut self, size: Vec2) {
+        let visible = self.panes.load(Ordering::SeqCst) && size.x >= self.min_width;
+        let mut shown = false;
+        self.view
+            .call_on_any(&Selector::Name("preview_pane"), &mut |view| {
+                if let Some(pane) = view.downcast_mut::<PreviewPane>() {
+                    if pane.is_visible() != visible {
+                        pane.set_visible(visible);
+                        shown = visible;
+                    }
+                }
+            });
+        if shown {
+            self.cb_sink.send(Box::new(show_selected)).ok();
+        }
+        self.view.layout(size);
+    }
+}
+
+/// Switches between the stacked and the panes layout.
+pub(super) fn toggle_panes(siv: &mut Cursive) {
+    let (panes, min_width) = siv
+        .with_user_data(|user_data: &mut UserData| {
+            (
+                !user_data.panes.fetch_xor(true, Ordering::SeqCst),
+                user_data.config.layout.min_width(),
+            )
+        })
+        .unwrap();
+    let message = if !panes {
+        String::from("Stacked layout")
+    } else if siv.screen_size().x < min_width {
+        format!("Panes layout, the preview shows from {} columns", min_width)
+    } else {
+        String::from("Panes layout")
+    };
+    set_status(siv, &message, Severity::Info);
+}
+
+fn is_visible(siv: &mut Cursive) -> bool {
+    siv.call_on_name("preview_pane", |pane: &mut PreviewPane| pane.is_visible())
+        .unwrap_or(false)
+}
+
+/// Shows the selected article of the content panel in the preview pane and
+/// marks it read after the configured delay if it's still selected then.
+pub(super) fn show_selected(siv: &mut Cursive) {
+    if !is_visible(siv) {
+        return;
+    }
+    let selected = siv
+        .call_on_name("content", |view: &mut SelectView<Article>| view.selection())
+        .flatten();
+    let Some(article) = selected else {
+        siv.call_on_name("preview", |dialog: &mut Dialog| {
+            dialog.set_content(DummyView);
+            dialog.set_title("Preview");
+        });
+        return;
+    };
+
+    let layout = article_layout(siv, &article, false, "preview_body");
+    siv.call_on_name("preview", |dialog: &mut Dialog| {
+        dialog.set_content(layout.scrollable());
+        dialog.set_title(article.title.as_str().truncate_ellipse(70));
+    });
+
+    let (delay, generation) = siv
+        .with_user_data(|user_data: &mut UserData| {
+            (
+                user_data.config.layout.mark_read_delay,
+                Arc::clone(&user_data.preview_generation),
+            )
+        })
+        .unwrap();
+    let shown = generation.fetch_add(1, Ordering::SeqCst) + 1;
+    let Some(delay) = delay.filter(|_| article.unread()) else {
+        return;
+    };
+    let article_id = article.id.clone();
+    let cb_sink = siv.cb_sink().clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_secs(delay));
+        if generation.load(Ordering::SeqCst) != shown {
+            return;
+        }
+        cb_sink
+            .send(Box::new(move |s: &mut Cursive| {
+                let still_selected = s
+                    .call_on_name("content", |view: &mut SelectView<Article>| view.selection())
+                    .flatten()
+                    .is_some_and(|selected| selected.id == article_id && selected.unread());
+                if still_selected && is_visible(s) {
+                    let db = open_db(s);
+                    mark_article_as_read(s, &article_id, db);
+                }
+            }))
+            .ok();
+    });
+}
@@ -1,31 +1,27 @@
-use crate::{category::Category, db::DB};
 use chrono::{Local, TimeZone};
 use directories::UserDirs;
-use rss::Channel;
+use std::env;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Output};
+use url::Url;
 
-pub fn fetch_feeds(category_list: &Vec<Category>) {
-    let db = DB::new();
-    for category in category_list {
-        for feed_link in category.feed_links() {
-            let output = fetch_page(feed_link.to_string());
-            match output {
-                Ok(result) => {
-                    let channel = Channel::read_from(&result.stdout[..]).unwrap();
-                    // FIXME: fix this
-                    /* db.create_feed(channel, &feed_link, &category.title)
-                    .unwrap(); */
-                }
-                Err(e) => println!("Error, {}", e),
-            }
-        }
-    }
-}
-
+/// Downloads `url`, failing on HTTP errors so error pages aren't taken for
+/// the content.
 pub fn fetch_page(url: String) -> io::Result<Output> {
-    Command::new("curl").arg("-s").arg("-S").arg(url).output()
+    let output = Command::new("curl")
+        .arg("-s")
+        .arg("-S")
+        .arg("-f")
+        .arg("-L")
+        .arg(url)
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(output)
 }
 
 pub fn formatted_pub_date(date: i64) -> String {
@@ -33,17 +29,91 @@ pub fn formatted_pub_date(date: i64) -> String {
     format!("{}", parsed.format("%d/%m/%Y %H:%M"))
 }
 
-pub fn get_config_dir() -> String {
-    let mut home = String::new();
-    if let Some(user_dirs) = UserDirs::new() {
-        match user_dirs.home_dir().to_str() {
-            Some(path) => home = path.to_string(),
-            None => panic!("Can't find home dir!"),
-        }
+const APP_DIR: &str = "news-rss";
+
+pub const SECONDS_IN_DAY: i64 = 24 * 60 * 60;
+
+fn home_dir() -> PathBuf {
+    match UserDirs::new() {
+        Some(user_dirs) => user_dirs.home_dir().to_path_buf(),
+        None => env::var_os("HOME").map(PathBuf::from).unwrap_or_default(),
+    }
+}
+
+/// Resolves an XDG base directory, ignoring unset, empty and relative values
+/// as required by the spec.
+fn xdg_dir(var: &str, fallback: &str) -> PathBuf {
+    match env::var_os(var).map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir.join(APP_DIR),
+        _ => home_dir().join(fallback).join(APP_DIR),
+    }
+}
+
+pub fn get_download_dir() -> PathBuf {
+    UserDirs::new()
+        .and_then(|user_dirs| user_dirs.download_dir().map(|dir| dir.to_path_buf()))
+        .unwrap_or_else(|| home_dir().join("Downloads"))
+}
+
+/// `$XDG_CONFIG_HOME/news-rss`, holds `config.toml`.
+pub fn get_config_dir() -> PathBuf {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// `$XDG_DATA_HOME/news-rss`, holds the databases.
+pub fn get_data_dir() -> PathBuf {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// `$XDG_CACHE_HOME/news-rss`, holds anything that can be re-downloaded.
+pub fn get_cache_dir() -> PathBuf {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+}
+
+pub fn formatted_size(bytes: i64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size.abs() >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Resolves a possibly relative `href` against `base`, skipping the ones that
+/// can't be opened outside of the page.
+pub fn resolve_link(base: Option<&Url>, href: &str) -> Option<String> {
+    let url = match Url::parse(href.trim()) {
+        Ok(url) => url,
+        Err(url::ParseError::RelativeUrlWithoutBase) => base?.join(href.trim()).ok()?,
+        Err(_) => return None,
+    };
+    if url.scheme() == "javascript" || url.scheme() == "data" {
+        return None;
+    }
+    Some(url.to_string())
+}
+
+/// `name` in `dir`, with a counter added before the extension when a file
+/// with that name is already there.
+pub fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    if !path.exists() {
+        return path;
     }
-    PathBuf::from(format!("{}/.config/news-rss", home))
-        .display()
-        .to_string()
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (name, String::new()),
+    };
+    (1..)
+        .map(|n| dir.join(format!("{}-{}{}", stem, n, extension)))
+        .find(|path| !path.exists())
+        .unwrap()
 }
 
 pub fn is_macos() -> bool {
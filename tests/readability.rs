@@ -0,0 +1,41 @@
+use news_rss::readability;
+use std::fs;
+
+fn fixture(name: &str) -> String {
+    fs::read_to_string(format!(
+        "{}/tests/fixtures/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+    .unwrap()
+}
+
+#[test]
+fn extracts_the_article_text() {
+    let content = readability::extract(&fixture("article.html")).unwrap();
+
+    assert!(content.contains("our build cache lived on a single large disk"));
+    assert!(content.contains("Both are on the list for next quarter"));
+    assert!(content.contains("<blockquote>"));
+}
+
+#[test]
+fn leaves_out_the_page_around_the_article() {
+    let content = readability::extract(&fixture("article.html")).unwrap();
+
+    for boilerplate in [
+        "We use cookies",
+        "Hunting down flaky tests",
+        "Subscribe to our newsletter",
+        "never looked back",
+        "All rights reserved",
+        "window.analytics",
+    ] {
+        assert!(!content.contains(boilerplate), "{}", boilerplate);
+    }
+}
+
+#[test]
+fn finds_nothing_on_a_page_of_links() {
+    assert_eq!(readability::extract(&fixture("navigation.html")), None);
+}